@@ -26,7 +26,7 @@ fn main() -> ! {
     // let mut watchdog = dp.IWDG.constrain();
 
     led.set_high().ok();
-    watchdog.start(20.millis());
+    watchdog.start(0.millis(), 20.millis());
 
     loop {}
 }
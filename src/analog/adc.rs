@@ -1,6 +1,7 @@
 //! # Analog to Digital converter
 use core::ptr;
 
+use crate::dma::{self, Direction, DmaMuxIndex, WordSize};
 use crate::gpio::*;
 use crate::rcc::{Enable, Rcc};
 use crate::stm32::ADC;
@@ -109,24 +110,50 @@ pub struct Adc {
     vref: Option<u32>,
 }
 
+/// A continuous ADC conversion feeding a DMA buffer, `wait()` or `stop()` give back the ADC,
+/// the DMA channel and the buffer
+pub struct AdcTransfer<CH> {
+    adc: Adc,
+    channel: CH,
+    buffer: &'static mut [u16],
+}
+
 /// Contains the calibration factors for the ADC which can be reused with [`Adc::set_calibration()`]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct CalibrationFactor(pub u8);
 
+/// ADC voltage regulator startup time (tADCVREG_SETUP) in microseconds
+const VREG_SETUP_US: u32 = 20;
+
+/// Temperature sensor calibration value, acquired at 30 °C and VDDA = 3.0 V
+const TS_CAL1: *const u16 = 0x1FFF_7568 as *const u16;
+/// Internal voltage reference calibration value, acquired at 30 °C and VDDA = 3.0 V
+const VREFINT_CAL: *const u16 = 0x1FFF_756A as *const u16;
+/// Temperature sensor average slope in µV/°C
+const TS_AVG_SLOPE_UV: i32 = 2_530;
+/// Temperature sensor startup time (tSTART) in CPU cycles at the highest system clock
+const TS_START_CYCLES: u32 = 48 * 25;
+/// Highest channel selectable in CHSELR
+const CHANNEL_MAX: u8 = 22;
+
 impl Adc {
+    /// Enables the ADC, waits for its voltage regulator and runs the calibration
     pub fn new(adc: ADC, rcc: &mut Rcc) -> Self {
         // Enable ADC clocks
         ADC::enable(rcc);
 
         adc.cr().modify(|_, w| w.advregen().bit(true));
+        cortex_m::asm::delay(rcc.clocks.ahb_clk().raw() / 1_000_000 * VREG_SETUP_US);
 
-        Self {
+        let mut adc = Self {
             rb: adc,
             sample_time: SampleTime::T_2,
             align: Align::Right,
             precision: Precision::B_12,
             vref: None,
-        }
+        };
+        adc.calibrate();
+        adc
     }
 
     /// Sets ADC source
@@ -149,8 +176,8 @@ impl Adc {
 
     /// Runs the calibration routine on the ADC
     ///
-    /// Wait for tADCVREG_SETUP after calling [`Self::new()`] before calibrating, to wait for the
-    /// ADC voltage regulator to stabilize.
+    /// [`Self::new()`] already calibrates the ADC, run it again after a significant VDDA or
+    /// temperature change.
     ///
     /// Do not call if an ADC reading is ongoing.
     pub fn calibrate(&mut self) {
@@ -211,6 +238,23 @@ impl Adc {
             .modify(|_, w| unsafe { w.ovsr().bits(ratio as u8) });
     }
 
+    /// Enables the hardware oversampler, accumulating `ratio` conversions shifted right by
+    /// `shift` bits (0 to 8) into each result
+    ///
+    /// A 256x ratio with a shift of 4 gives a 16-bit result. Call [`Self::oversampling_enable()`]
+    /// with `false` to go back to single conversions.
+    pub fn set_oversampling(&mut self, ratio: OversamplingRatio, shift: u8) {
+        assert!(shift <= 8);
+        self.rb.cfgr2().modify(|_, w| unsafe {
+            w.ovsr()
+                .bits(ratio as u8)
+                .ovss()
+                .bits(shift)
+                .ovse()
+                .set_bit()
+        });
+    }
+
     pub fn oversampling_enable(&mut self, enable: bool) {
         self.rb.cfgr2().modify(|_, w| w.ovse().bit(enable));
     }
@@ -230,6 +274,69 @@ impl Adc {
         self.rb.ier().modify(|_, w| w.eocie().clear_bit()); // end of sequence interupt disable
     }
 
+    /// Measures VDDA in millivolts using the internal voltage reference and its factory
+    /// calibration
+    ///
+    /// The result is also used by the following [`Self::read_voltage()`] calls.
+    pub fn read_vref(&mut self) -> nb::Result<u16, ()> {
+        let mut vref = VRef::new();
+        let raw = self.read_internal(&mut vref)?;
+        let vref_cal = unsafe { ptr::read_volatile(VREFINT_CAL) } as u32;
+        let vdda = 3_000 * vref_cal / raw.max(1);
+        self.vref = Some(vdda);
+        Ok(vdda as u16)
+    }
+
+    /// Reads the die temperature in tenths of a degree Celsius
+    ///
+    /// The sensor has a single factory calibration point at 30 °C and the conversion relies on
+    /// the typical average slope, so expect a few degrees of error far from 30 °C. VDDA is
+    /// measured first if it is not known yet.
+    pub fn read_temperature(&mut self) -> nb::Result<i16, ()> {
+        let vdda = match self.vref {
+            Some(vdda) => vdda,
+            None => self.read_vref()? as u32,
+        };
+
+        let mut vtemp = VTemp::new();
+        let raw = self.read_internal(&mut vtemp)?;
+
+        // scale the reading to the VDDA of the calibration, in i64 since the distance to
+        // TS_CAL1 times 3_000_000 overflows 32 bits past 715 LSB
+        let raw = i64::from(raw) * i64::from(vdda) / 3_000;
+        let ts_cal1 = i64::from(unsafe { ptr::read_volatile(TS_CAL1) });
+        let delta_uv = (raw - ts_cal1) * 3_000_000 / 4_095;
+        let tenths = 300 + delta_uv * 10 / i64::from(TS_AVG_SLOPE_UV);
+        Ok(tenths.clamp(i16::MIN.into(), i16::MAX.into()) as i16)
+    }
+
+    /// Converts an internal channel with the longest sampling time at 12-bit right-aligned,
+    /// the channel is enabled for the conversion if needed
+    fn read_internal<CH>(&mut self, ch: &mut CH) -> nb::Result<u32, ()>
+    where
+        CH: Channel<Adc, ID = u8> + InternalChannel,
+    {
+        let enabled = ch.enabled(self);
+        if !enabled {
+            ch.enable(self);
+            cortex_m::asm::delay(TS_START_CYCLES);
+        }
+
+        let (sample_time, precision) = (self.sample_time, self.precision);
+        let align = core::mem::replace(&mut self.align, Align::Right);
+        self.sample_time = SampleTime::T_160;
+        self.precision = Precision::B_12;
+        let res = self.read(ch);
+        self.sample_time = sample_time;
+        self.precision = precision;
+        self.align = align;
+
+        if !enabled {
+            ch.disable(self);
+        }
+        res
+    }
+
     pub fn read_voltage<PIN: Channel<Adc, ID = u8>>(
         &mut self,
         pin: &mut PIN,
@@ -260,6 +367,145 @@ impl Adc {
         })
     }
 
+    /// Starts converting `channels` back to back, the DMA channel fills `buffer` once and the
+    /// conversions stop on [`AdcTransfer::wait()`]
+    ///
+    /// The channels are converted in ascending order whatever their order in `channels`, the
+    /// results are interleaved in `buffer`. `buffer` must hold between 1 and 65535 samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is empty or holds a channel above 22, the conversions would never
+    /// start or would select reserved bits.
+    pub fn start_continuous_dma<CH: dma::Channel>(
+        self,
+        channels: &[u8],
+        channel: CH,
+        buffer: &'static mut [u16],
+    ) -> AdcTransfer<CH> {
+        self.start_dma(channels, channel, buffer, false)
+    }
+
+    /// Same as [`Self::start_continuous_dma()`] but the DMA channel wraps around at the end of
+    /// `buffer` and the conversions run until [`AdcTransfer::stop()`]
+    ///
+    /// Use [`AdcTransfer::is_half_done()`] and [`AdcTransfer::is_done()`] to know which half of
+    /// `buffer` can be read. Panics like [`Self::start_continuous_dma()`].
+    pub fn start_circular_dma<CH: dma::Channel>(
+        self,
+        channels: &[u8],
+        channel: CH,
+        buffer: &'static mut [u16],
+    ) -> AdcTransfer<CH> {
+        self.start_dma(channels, channel, buffer, true)
+    }
+
+    fn start_dma<CH: dma::Channel>(
+        mut self,
+        channels: &[u8],
+        mut channel: CH,
+        buffer: &'static mut [u16],
+        circular: bool,
+    ) -> AdcTransfer<CH> {
+        assert!(!buffer.is_empty() && buffer.len() <= u16::MAX as usize);
+        assert!(!channels.is_empty(), "no ADC channel to convert");
+        assert!(
+            channels.iter().all(|&ch| ch <= CHANNEL_MAX),
+            "ADC channel above {}",
+            CHANNEL_MAX
+        );
+        let chselr = channels.iter().fold(0u32, |mask, ch| mask | 1 << ch);
+
+        // RES, CONT and DMAEN can only be written while the ADC is disabled
+        self.rb.cfgr1().modify(|_, w| unsafe {
+            w.res()
+                .bits(self.precision as u8)
+                .align()
+                .bit(self.align == Align::Left)
+                .chselrmod()
+                .clear_bit()
+                .cont()
+                .set_bit()
+                .dmaen()
+                .set_bit()
+                .dmacfg()
+                .bit(circular)
+        });
+        self.power_up();
+
+        self.rb
+            .smpr()
+            .modify(|_, w| unsafe { w.smp1().bits(self.sample_time as u8) });
+
+        self.rb.isr().write(|w| w.ccrdy().bit(true));
+        self.rb.chselr0().write(|w| unsafe { w.bits(chselr) });
+        while self.rb.isr().read().ccrdy().bit_is_clear() {}
+
+        let mut config = dma::Config::default().request(DmaMuxIndex::ADC);
+        if circular {
+            config = config.circular();
+        }
+        dma::setup_channel(
+            &mut channel,
+            self.rb.dr().as_ptr() as u32,
+            buffer.as_mut_ptr() as u32,
+            buffer.len(),
+            WordSize::BITS16,
+            Direction::FromPeripheral,
+            config,
+        );
+
+        self.rb.isr().write(|w| w.ovr().bit(true).eos().bit(true));
+        self.rb.cr().modify(|_, w| w.adstart().set_bit());
+
+        AdcTransfer {
+            adc: self,
+            channel,
+            buffer,
+        }
+    }
+
+    /// Enables the analog watchdog 1 on `pin` with the `low` and `high` thresholds (12-bit raw
+    /// values) and its interrupt
+    ///
+    /// The watchdog checks every conversion of the channel, started by a `read()` or a DMA
+    /// transfer, the ADC interrupt fires on a result outside `low..=high`. The thresholds are
+    /// compared to the 12-bit result, whatever the configured precision.
+    pub fn configure_watchdog<PIN: Channel<Adc, ID = u8>>(
+        &mut self,
+        _pin: &PIN,
+        low: u16,
+        high: u16,
+    ) {
+        self.rb.awd1tr().write(|w| w.lt1().set(low).ht1().set(high));
+        self.rb.cfgr1().modify(|_, w| unsafe {
+            w.awd1ch()
+                .bits(PIN::channel())
+                .awd1sgl()
+                .set_bit()
+                .awd1en()
+                .set_bit()
+        });
+        self.rb.isr().write(|w| w.awd1().bit(true));
+        self.rb.ier().modify(|_, w| w.awd1ie().set_bit());
+    }
+
+    /// Disables the analog watchdog 1 and its interrupt
+    pub fn disable_watchdog(&mut self) {
+        self.rb.ier().modify(|_, w| w.awd1ie().clear_bit());
+        self.rb.cfgr1().modify(|_, w| w.awd1en().clear_bit());
+    }
+
+    /// Returns `true` if a conversion fell outside the analog watchdog 1 thresholds
+    pub fn is_watchdog_pending(&self) -> bool {
+        self.rb.isr().read().awd1().bit_is_set()
+    }
+
+    /// Clears the analog watchdog 1 flag
+    pub fn clear_watchdog(&mut self) {
+        self.rb.isr().write(|w| w.awd1().bit(true));
+    }
+
     pub fn release(self) -> ADC {
         self.rb
     }
@@ -277,6 +523,57 @@ impl Adc {
     }
 }
 
+impl<CH: dma::Channel> AdcTransfer<CH> {
+    /// Returns `true` once the first half of the buffer has been filled
+    pub fn is_half_done(&self) -> bool {
+        self.channel.is_half_complete()
+    }
+
+    /// Returns `true` once the whole buffer has been filled
+    pub fn is_done(&self) -> bool {
+        self.channel.is_complete()
+    }
+
+    /// Clears the half and full buffer flags, call it after reading a half in circular mode
+    pub fn clear_flags(&mut self) {
+        self.channel.clear_flags();
+    }
+
+    /// Blocks until the whole buffer has been filled and stops the conversions
+    pub fn wait(self) -> (Adc, CH, &'static mut [u16]) {
+        while !self.channel.is_complete() {}
+        self.stop()
+    }
+
+    /// Stops the conversions and the DMA channel
+    pub fn stop(self) -> (Adc, CH, &'static mut [u16]) {
+        let AdcTransfer {
+            mut adc,
+            mut channel,
+            buffer,
+        } = self;
+
+        adc.rb.cr().modify(|_, w| w.adstp().set_bit());
+        while adc.rb.cr().read().adstart().bit_is_set() {}
+        adc.power_down();
+
+        channel.disable();
+        channel.clear_flags();
+        channel.set_circular_mode(false);
+
+        adc.rb.cfgr1().modify(|_, w| {
+            w.cont()
+                .clear_bit()
+                .dmaen()
+                .clear_bit()
+                .dmacfg()
+                .clear_bit()
+        });
+
+        (adc, channel, buffer)
+    }
+}
+
 pub trait AdcExt {
     fn constrain(self, rcc: &mut Rcc) -> Adc;
 }
@@ -360,21 +657,24 @@ where
     type Error = ();
 
     fn read(&mut self, _pin: &mut PIN) -> nb::Result<WORD, Self::Error> {
-        self.power_up();
+        // RES can only be written while the ADC is disabled
         self.rb.cfgr1().modify(|_, w| unsafe {
             w.res()
                 .bits(self.precision as u8)
                 .align()
                 .bit(self.align == Align::Left)
         });
+        self.power_up();
 
         self.rb
             .smpr()
             .modify(|_, w| unsafe { w.smp1().bits(self.sample_time as u8) });
 
+        self.rb.isr().write(|w| w.ccrdy().bit(true));
         self.rb
             .chselr0()
             .modify(|_, w| unsafe { w.bits(1 << PIN::channel()) });
+        while self.rb.isr().read().ccrdy().bit_is_clear() {}
 
         self.rb.isr().modify(|_, w| w.eos().bit(true));
         self.rb.cr().modify(|_, w| w.adstart().set_bit());
@@ -392,6 +692,13 @@ where
     }
 }
 
+/// Internal channels switched on through the common configuration register
+trait InternalChannel {
+    fn enable(&mut self, adc: &mut Adc);
+    fn disable(&mut self, adc: &mut Adc);
+    fn enabled(&self, adc: &Adc) -> bool;
+}
+
 macro_rules! int_adc {
     ($($Chan:ident: ($chan:expr, $en:ident)),+ $(,)*) => {
         $(
@@ -415,6 +722,20 @@ macro_rules! int_adc {
                 }
             }
 
+            impl InternalChannel for $Chan {
+                fn enable(&mut self, adc: &mut Adc) {
+                    $Chan::enable(self, adc)
+                }
+
+                fn disable(&mut self, adc: &mut Adc) {
+                    $Chan::disable(self, adc)
+                }
+
+                fn enabled(&self, adc: &Adc) -> bool {
+                    $Chan::enabled(self, adc)
+                }
+            }
+
             impl Default for $Chan {
                 fn default() -> $Chan {
                     $Chan::new()
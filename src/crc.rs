@@ -1,11 +1,23 @@
 //! CRC calculation unit
 //!
+//! The default configuration is the Ethernet CRC-32 (as computed by zlib), other CRCs are
+//! selected through the [`Config`] builder.
+//!
 //! Usage example:
 //! ```
-//! let crc = dp.RCC.constrain();
+//! let mut crc = dp.CRC.constrain(&mut rcc).freeze();
+//! crc.feed(b"123456789");
+//! assert!(crc.result() == 0xcbf4_3926);
 //!
-//! // Lets use the CRC-16-CCITT polynomial
-//! let mut crc = crc.polynomial(crc::Polynomial::L16(0x1021)).freeze();
+//! // Lets use the CRC-16-CCITT polynomial, without any bit reversal
+//! let mut crc = dp
+//!     .CRC
+//!     .constrain(&mut rcc)
+//!     .polynomial(crc::Polynomial::L16(0x1021))
+//!     .input_bit_reversal(None)
+//!     .output_bit_reversal(false)
+//!     .output_xor(0)
+//!     .freeze();
 //!
 //! let data = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13];
 //! crc.feed(&data);
@@ -35,8 +47,9 @@ impl CrcExt for CRC {
         Config {
             initial_value: 0xffff_ffff,
             polynomial: Polynomial::L32(0x04c1_1db7),
-            input_bit_reversal: None,
-            output_bit_reversal: false,
+            input_bit_reversal: Some(BitReversal::ByByte),
+            output_bit_reversal: true,
+            output_xor: 0xffff_ffff,
         }
     }
 }
@@ -69,6 +82,7 @@ pub struct Config {
     polynomial: Polynomial,
     input_bit_reversal: Option<BitReversal>,
     output_bit_reversal: bool,
+    output_xor: u32,
 }
 
 impl Config {
@@ -100,16 +114,25 @@ impl Config {
         self
     }
 
+    /// Sets the value XORed with the result, the hardware has no final XOR so
+    /// [`Crc::result()`] applies it.
+    pub fn output_xor(mut self, xor: u32) -> Self {
+        self.output_xor = xor;
+
+        self
+    }
+
     /// Freezes the peripheral, making the configuration take effect.
     pub fn freeze(self) -> Crc {
         let crc = unsafe { &(*CRC::ptr()) };
 
-        let (poly, poly_bits, init) = match self.polynomial {
-            Polynomial::L7(val) => ((val & 0x7f) as u32, 0b11, self.initial_value & 0x7f),
-            Polynomial::L8(val) => (val as u32, 0b10, self.initial_value & 0xff),
-            Polynomial::L16(val) => (val as u32, 0b01, self.initial_value & 0xffff),
-            Polynomial::L32(val) => (val, 0b00, self.initial_value),
+        let (poly, poly_bits, mask) = match self.polynomial {
+            Polynomial::L7(val) => ((val & 0x7f) as u32, 0b11, 0x7f),
+            Polynomial::L8(val) => (val as u32, 0b10, 0xff),
+            Polynomial::L16(val) => (val as u32, 0b01, 0xffff),
+            Polynomial::L32(val) => (val, 0b00, 0xffff_ffff),
         };
+        let init = self.initial_value & mask;
 
         let in_rev_bits = match self.input_bit_reversal {
             None => 0b00,
@@ -135,12 +158,16 @@ impl Config {
             }
         });
 
-        Crc {}
+        Crc {
+            output_xor: self.output_xor & mask,
+        }
     }
 }
 
 /// Constrained CRC peripheral.
-pub struct Crc {}
+pub struct Crc {
+    output_xor: u32,
+}
 
 impl Crc {
     /// This will reset the CRC to its initial condition.
@@ -168,9 +195,11 @@ impl Crc {
     #[inline]
     pub fn feed(&mut self, data: &[u8]) {
         let crc = unsafe { &(*CRC::ptr()) };
+        // the access size sets the number of bits processed, DR must be written byte-wise
+        let dr = crc.dr().as_ptr() as *mut u8;
         for byte in data {
             unsafe {
-                crc.dr().write(|w| w.bits(*byte as _));
+                core::ptr::write_volatile(dr, *byte);
             }
         }
     }
@@ -192,7 +221,7 @@ impl Crc {
     pub fn peek_result(&self) -> u32 {
         let crc = unsafe { &(*CRC::ptr()) };
 
-        crc.dr().read().bits()
+        crc.dr().read().bits() ^ self.output_xor
     }
 }
 
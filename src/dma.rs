@@ -0,0 +1,595 @@
+//! Direct Memory Access controller
+//!
+//! The DMA channels are split off `dp.DMA` as typed handles, the DMAMUX request line routed to
+//! a channel is selected with [`Channel::select_peripheral`].
+//!
+//! [`Channel::start`] programs a whole transfer at once and returns a [`Transfer`] that gives
+//! the channel and the buffer back on [`Transfer::wait`].
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::stm32::{DMA, DMAMUX};
+
+/// Extension trait to split a DMA peripheral into independent channels
+pub trait DmaExt {
+    /// The type to split the DMA into
+    type Channels;
+
+    /// Splits the DMA peripheral into channels
+    fn split(self, rcc: &mut Rcc) -> Self::Channels;
+}
+
+/// DMAMUX request lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaMuxIndex {
+    ADC = 5,
+    I2C1_RX = 10,
+    I2C1_TX = 11,
+    #[cfg(feature = "stm32c071")]
+    I2C2_RX = 12,
+    #[cfg(feature = "stm32c071")]
+    I2C2_TX = 13,
+    SPI1_RX = 16,
+    SPI1_TX = 17,
+    #[cfg(feature = "stm32c071")]
+    SPI2_RX = 18,
+    #[cfg(feature = "stm32c071")]
+    SPI2_TX = 19,
+    USART1_RX = 50,
+    USART1_TX = 51,
+    USART2_RX = 52,
+    USART2_TX = 53,
+}
+
+/// Channel priority level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+    VeryHigh,
+}
+
+/// Transfer direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Peripheral to memory
+    FromPeripheral,
+    /// Memory to peripheral
+    FromMemory,
+}
+
+/// Size of a single data item, the same size is used for peripheral and memory accesses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordSize {
+    BITS8,
+    BITS16,
+    BITS32,
+}
+
+/// Channel interrupt events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    HalfTransfer,
+    TransferComplete,
+    TransferError,
+}
+
+/// Data items the DMA can move, the item size follows the buffer element type
+pub trait Word: crate::Sealed {
+    const SIZE: WordSize;
+}
+
+impl crate::Sealed for u8 {}
+impl Word for u8 {
+    const SIZE: WordSize = WordSize::BITS8;
+}
+
+impl crate::Sealed for u16 {}
+impl Word for u16 {
+    const SIZE: WordSize = WordSize::BITS16;
+}
+
+impl crate::Sealed for u32 {}
+impl Word for u32 {
+    const SIZE: WordSize = WordSize::BITS32;
+}
+
+/// Transfer configuration for [`Channel::start`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub(crate) request: Option<DmaMuxIndex>,
+    pub(crate) priority: Priority,
+    pub(crate) circular: bool,
+    pub(crate) peripheral_increment: bool,
+    pub(crate) memory_increment: bool,
+}
+
+impl Config {
+    /// Routes this DMAMUX request line to the channel
+    pub fn request(mut self, index: DmaMuxIndex) -> Self {
+        self.request = Some(index);
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Restarts from the beginning of the buffer once it has been filled or drained
+    pub fn circular(mut self) -> Self {
+        self.circular = true;
+        self
+    }
+
+    /// Increments the peripheral address after each item
+    pub fn peripheral_increment(mut self) -> Self {
+        self.peripheral_increment = true;
+        self
+    }
+
+    /// Reads or writes the same memory item over and over, e.g. dummy bytes clocking a read
+    pub(crate) fn fixed_memory(mut self) -> Self {
+        self.memory_increment = false;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            request: None,
+            priority: Priority::Low,
+            circular: false,
+            peripheral_increment: false,
+            memory_increment: true,
+        }
+    }
+}
+
+/// Programs and enables `channel` for `len` items of `size` between the peripheral register
+/// at `peripheral` and `memory`, clearing its flags first
+///
+/// This is the setup shared by [`Channel::start`], [`CircularBuffer`] and the peripheral DMA
+/// transfers, `len` must be between 1 and 65535.
+pub(crate) fn setup_channel<CH: Channel>(
+    channel: &mut CH,
+    peripheral: u32,
+    memory: u32,
+    len: usize,
+    size: WordSize,
+    direction: Direction,
+    config: Config,
+) {
+    assert!(len > 0 && len <= u16::MAX as usize);
+
+    channel.disable();
+    channel.clear_flags();
+    if let Some(index) = config.request {
+        channel.select_peripheral(index);
+    }
+    channel.set_direction(direction);
+    channel.set_word_size(size);
+    channel.set_priority_level(config.priority);
+    channel.set_circular_mode(config.circular);
+    channel.set_memory_to_memory(false);
+    channel.set_peripheral_address(peripheral, config.peripheral_increment);
+    channel.set_memory_address(memory, config.memory_increment);
+    channel.set_transfer_length(len as u16);
+    channel.enable();
+}
+
+/// A DMA transfer in progress, `wait()` gives back the channel and the buffer
+pub struct Transfer<CH, BUF> {
+    channel: CH,
+    buffer: BUF,
+}
+
+/// A memory to memory copy, `wait()` gives back the channel and the `(src, dst)` slices
+pub type MemoryTransfer<CH, W> = Transfer<CH, (&'static [W], &'static mut [W])>;
+
+impl<CH: Channel, BUF> Transfer<CH, BUF> {
+    /// Returns `true` once all the items have been transferred
+    pub fn is_done(&self) -> bool {
+        self.channel.is_complete()
+    }
+
+    /// Returns `true` once the first half of the items have been transferred
+    pub fn is_half_done(&self) -> bool {
+        self.channel.is_half_complete()
+    }
+
+    /// Returns `true` if a transfer error stopped the channel
+    pub fn is_error(&self) -> bool {
+        self.channel.is_error()
+    }
+
+    /// Clears the channel flags, needed between two halves in circular mode
+    pub fn clear_flags(&mut self) {
+        self.channel.clear_flags();
+    }
+
+    /// Blocks until the transfer is complete or failed, then disables the channel
+    ///
+    /// The flags are left untouched so a failure can still be read with
+    /// [`Channel::is_error`].
+    pub fn wait(self) -> (CH, BUF) {
+        while !self.channel.is_complete() && !self.channel.is_error() {}
+        self.stop()
+    }
+
+    /// Disables the channel right away and gives back the channel and the buffer
+    pub fn stop(self) -> (CH, BUF) {
+        let Transfer {
+            mut channel,
+            buffer,
+        } = self;
+        channel.disable();
+        channel.set_circular_mode(false);
+        channel.set_memory_to_memory(false);
+        (channel, buffer)
+    }
+}
+
+/// Double buffering over a circular transfer of `N` items
+///
+/// The DMA keeps going around the buffer, [`safe_half`](Self::safe_half) hands out the half it
+/// is not accessing so it can be processed (peripheral to memory) or refilled (memory to
+/// peripheral) while the other half is in flight.
+pub struct CircularBuffer<CH, W: 'static, const N: usize> {
+    channel: CH,
+    buffer: &'static mut [W; N],
+}
+
+impl<CH: Channel, W: Word, const N: usize> CircularBuffer<CH, W, N> {
+    /// Starts a circular transfer between the peripheral register at `peripheral_address` and
+    /// `buffer`
+    ///
+    /// `N` must be even and at most 65535.
+    pub fn new(
+        mut channel: CH,
+        peripheral_address: u32,
+        buffer: &'static mut [W; N],
+        direction: Direction,
+        config: Config,
+    ) -> Self {
+        assert!(N % 2 == 0);
+
+        setup_channel(
+            &mut channel,
+            peripheral_address,
+            buffer.as_mut_ptr() as u32,
+            N,
+            W::SIZE,
+            direction,
+            config.circular(),
+        );
+
+        CircularBuffer { channel, buffer }
+    }
+
+    /// Returns `true` once the DMA has gone past the first half
+    pub fn half_complete(&self) -> bool {
+        self.channel.is_half_complete()
+    }
+
+    /// Returns `true` once the DMA has wrapped around to the first half
+    pub fn complete(&self) -> bool {
+        self.channel.is_complete()
+    }
+
+    /// Returns the half the DMA is not accessing if it crossed a half boundary since the last
+    /// call, and clears the half and transfer complete flags
+    ///
+    /// If both flags were set, the caller fell behind and a whole half went by unprocessed.
+    pub fn safe_half(&mut self) -> Option<&mut [W]> {
+        if !self.channel.is_half_complete() && !self.channel.is_complete() {
+            return None;
+        }
+        self.channel.clear_event(Event::HalfTransfer);
+        self.channel.clear_event(Event::TransferComplete);
+        compiler_fence(Ordering::SeqCst);
+
+        // NDTR counts down from N, the DMA is in the first half while more than N / 2 remain
+        let in_first_half = self.channel.transfer_remaining() as usize > N / 2;
+        let (first, second) = self.buffer.split_at_mut(N / 2);
+        Some(if in_first_half { second } else { first })
+    }
+
+    /// Stops the transfer and gives back the channel and the buffer
+    pub fn stop(self) -> (CH, &'static mut [W; N]) {
+        let CircularBuffer {
+            mut channel,
+            buffer,
+        } = self;
+        channel.disable();
+        channel.clear_flags();
+        channel.set_circular_mode(false);
+        compiler_fence(Ordering::SeqCst);
+        (channel, buffer)
+    }
+}
+
+/// Common interface of the DMA channels
+pub trait Channel: crate::Sealed {
+    /// Routes the DMAMUX request line to this channel
+    fn select_peripheral(&mut self, index: DmaMuxIndex);
+
+    /// Sets the peripheral address and whether it is incremented after each item
+    fn set_peripheral_address(&mut self, address: u32, inc: bool);
+
+    /// Sets the memory address and whether it is incremented after each item
+    fn set_memory_address(&mut self, address: u32, inc: bool);
+
+    /// Sets the number of items to transfer
+    fn set_transfer_length(&mut self, len: u16);
+
+    fn set_word_size(&mut self, wsize: WordSize);
+
+    fn set_priority_level(&mut self, priority: Priority);
+
+    fn set_direction(&mut self, direction: Direction);
+
+    /// Restarts the transfer from the programmed addresses once the length has been exhausted
+    fn set_circular_mode(&mut self, circular: bool);
+
+    /// Makes the channel copy memory to memory as fast as possible, without peripheral requests
+    fn set_memory_to_memory(&mut self, enable: bool);
+
+    /// Returns the number of items still to be transferred
+    fn transfer_remaining(&self) -> u16;
+
+    fn enable(&mut self);
+
+    fn disable(&mut self);
+
+    /// Returns `true` once the transfer complete flag is set
+    fn is_complete(&self) -> bool;
+
+    /// Returns `true` once the first half of the items have been transferred
+    fn is_half_complete(&self) -> bool;
+
+    /// Returns `true` if a transfer error occurred
+    fn is_error(&self) -> bool;
+
+    /// Clears all the interrupt flags of this channel
+    fn clear_flags(&mut self);
+
+    /// Clears the flag of `event` only
+    fn clear_event(&mut self, event: Event);
+
+    /// Enables the interrupt for `event`
+    fn listen(&mut self, event: Event);
+
+    /// Disables the interrupt for `event`
+    fn unlisten(&mut self, event: Event);
+
+    /// Programs and enables a transfer between the peripheral register at `peripheral_address`
+    /// and `buffer`
+    ///
+    /// `buffer` must hold between 1 and 65535 items.
+    fn start<W: Word>(
+        mut self,
+        peripheral_address: u32,
+        buffer: &'static mut [W],
+        direction: Direction,
+        config: Config,
+    ) -> Transfer<Self, &'static mut [W]>
+    where
+        Self: Sized,
+    {
+        setup_channel(
+            &mut self,
+            peripheral_address,
+            buffer.as_mut_ptr() as u32,
+            buffer.len(),
+            W::SIZE,
+            direction,
+            config,
+        );
+
+        Transfer {
+            channel: self,
+            buffer,
+        }
+    }
+
+    /// Copies `src` into `dst`, both addresses are incremented after each item
+    ///
+    /// The channel is given back in `Err` if the slices have different lengths or do not hold
+    /// between 1 and 65535 items.
+    fn memory_to_memory<W: Word>(
+        mut self,
+        src: &'static [W],
+        dst: &'static mut [W],
+    ) -> Result<MemoryTransfer<Self, W>, Self>
+    where
+        Self: Sized,
+    {
+        if src.len() != dst.len() || src.is_empty() || src.len() > u16::MAX as usize {
+            return Err(self);
+        }
+
+        self.disable();
+        self.clear_flags();
+        // with MEM2MEM the peripheral side is read when DIR selects a peripheral to memory copy
+        self.set_direction(Direction::FromPeripheral);
+        self.set_word_size(W::SIZE);
+        self.set_circular_mode(false);
+        self.set_memory_to_memory(true);
+        self.set_peripheral_address(src.as_ptr() as u32, true);
+        self.set_memory_address(dst.as_mut_ptr() as u32, true);
+        self.set_transfer_length(src.len() as u16);
+        self.enable();
+
+        Ok(Transfer {
+            channel: self,
+            buffer: (src, dst),
+        })
+    }
+}
+
+macro_rules! dma {
+    ($($CX:ident: ($chX:ident, $ch:expr),)+) => {
+        /// DMA channels
+        pub struct Channels {
+            $(pub $chX: $CX,)+
+        }
+
+        impl DmaExt for DMA {
+            type Channels = Channels;
+
+            fn split(self, rcc: &mut Rcc) -> Channels {
+                DMA::enable(rcc);
+                DMA::reset(rcc);
+
+                Channels {
+                    $($chX: $CX { _0: () },)+
+                }
+            }
+        }
+
+        $(
+            /// DMA channel
+            pub struct $CX {
+                _0: (),
+            }
+
+            impl crate::Sealed for $CX {}
+
+            impl $CX {
+                fn ch(&self) -> &crate::stm32::dma::CH {
+                    unsafe { (*DMA::ptr()).ch($ch) }
+                }
+            }
+
+            impl Channel for $CX {
+                fn select_peripheral(&mut self, index: DmaMuxIndex) {
+                    let dmamux = unsafe { &*DMAMUX::ptr() };
+                    dmamux
+                        .ccr($ch)
+                        .modify(|_, w| unsafe { w.dmareq_id().bits(index as u8) });
+                }
+
+                fn set_peripheral_address(&mut self, address: u32, inc: bool) {
+                    self.ch().par().write(|w| unsafe { w.pa().bits(address) });
+                    self.ch().cr().modify(|_, w| w.pinc().bit(inc));
+                }
+
+                fn set_memory_address(&mut self, address: u32, inc: bool) {
+                    self.ch().mar().write(|w| unsafe { w.ma().bits(address) });
+                    self.ch().cr().modify(|_, w| w.minc().bit(inc));
+                }
+
+                fn set_transfer_length(&mut self, len: u16) {
+                    self.ch().ndtr().write(|w| w.ndt().set(len));
+                }
+
+                fn set_word_size(&mut self, wsize: WordSize) {
+                    self.ch().cr().modify(|_, w| match wsize {
+                        WordSize::BITS8 => w.psize().bits8().msize().bits8(),
+                        WordSize::BITS16 => w.psize().bits16().msize().bits16(),
+                        WordSize::BITS32 => w.psize().bits32().msize().bits32(),
+                    });
+                }
+
+                fn set_priority_level(&mut self, priority: Priority) {
+                    self.ch().cr().modify(|_, w| match priority {
+                        Priority::Low => w.pl().low(),
+                        Priority::Medium => w.pl().medium(),
+                        Priority::High => w.pl().high(),
+                        Priority::VeryHigh => w.pl().very_high(),
+                    });
+                }
+
+                fn set_direction(&mut self, direction: Direction) {
+                    self.ch().cr().modify(|_, w| {
+                        w.dir().bit(direction == Direction::FromMemory)
+                    });
+                }
+
+                fn set_circular_mode(&mut self, circular: bool) {
+                    self.ch().cr().modify(|_, w| w.circ().bit(circular));
+                }
+
+                fn set_memory_to_memory(&mut self, enable: bool) {
+                    self.ch().cr().modify(|_, w| w.mem2mem().bit(enable));
+                }
+
+                fn transfer_remaining(&self) -> u16 {
+                    self.ch().ndtr().read().ndt().bits()
+                }
+
+                fn enable(&mut self) {
+                    self.ch().cr().modify(|_, w| w.en().set_bit());
+                }
+
+                fn disable(&mut self) {
+                    self.ch().cr().modify(|_, w| w.en().clear_bit());
+                }
+
+                fn is_complete(&self) -> bool {
+                    let dma = unsafe { &*DMA::ptr() };
+                    dma.isr().read().tcif($ch).bit_is_set()
+                }
+
+                fn is_half_complete(&self) -> bool {
+                    let dma = unsafe { &*DMA::ptr() };
+                    dma.isr().read().htif($ch).bit_is_set()
+                }
+
+                fn is_error(&self) -> bool {
+                    let dma = unsafe { &*DMA::ptr() };
+                    dma.isr().read().teif($ch).bit_is_set()
+                }
+
+                fn clear_flags(&mut self) {
+                    let dma = unsafe { &*DMA::ptr() };
+                    dma.ifcr().write(|w| w.cgif($ch).clear());
+                }
+
+                fn clear_event(&mut self, event: Event) {
+                    let dma = unsafe { &*DMA::ptr() };
+                    dma.ifcr().write(|w| match event {
+                        Event::HalfTransfer => w.chtif($ch).clear(),
+                        Event::TransferComplete => w.ctcif($ch).clear(),
+                        Event::TransferError => w.cteif($ch).clear(),
+                    });
+                }
+
+                fn listen(&mut self, event: Event) {
+                    self.ch().cr().modify(|_, w| match event {
+                        Event::HalfTransfer => w.htie().set_bit(),
+                        Event::TransferComplete => w.tcie().set_bit(),
+                        Event::TransferError => w.teie().set_bit(),
+                    });
+                }
+
+                fn unlisten(&mut self, event: Event) {
+                    self.ch().cr().modify(|_, w| match event {
+                        Event::HalfTransfer => w.htie().clear_bit(),
+                        Event::TransferComplete => w.tcie().clear_bit(),
+                        Event::TransferError => w.teie().clear_bit(),
+                    });
+                }
+            }
+        )+
+    }
+}
+
+#[cfg(any(feature = "stm32c011", feature = "stm32c031"))]
+dma!(
+    C1: (ch1, 0),
+    C2: (ch2, 1),
+    C3: (ch3, 2),
+);
+
+#[cfg(feature = "stm32c071")]
+dma!(
+    C1: (ch1, 0),
+    C2: (ch2, 1),
+    C3: (ch3, 2),
+    C4: (ch4, 3),
+    C5: (ch5, 4),
+);
@@ -1,6 +1,8 @@
 //! External interrupt controller
 use crate::gpio::SignalEdge;
 use crate::stm32::EXTI;
+use core::sync::atomic::{AtomicU16, Ordering};
+use cortex_m::interrupt;
 
 /// EXTI trigger event
 #[derive(Eq, PartialEq, PartialOrd, Clone, Copy, Debug)]
@@ -55,12 +57,37 @@ impl Event {
 
 const TRIGGER_MAX: u8 = 15;
 
+/// GPIO lines routed through `ExtiExt::claim_line`, bit `n` for line `n`
+///
+/// EXTICR resets to port A for every line, the register alone can't tell a claimed line from
+/// an unused one.
+static CLAIMED: AtomicU16 = AtomicU16::new(0);
+
+/// EXTI routing error
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum Error {
+    /// The line is already claimed or unmasked for the same pin number of another port,
+    /// `port` is the EXTICR index of that port (0 for GPIOA, 1 for GPIOB, ...)
+    LineInUse { port: u8 },
+}
+
 pub trait ExtiExt {
     fn wakeup(&self, ev: Event);
     fn listen(&self, ev: Event, edge: SignalEdge);
     fn unlisten(&self, ev: Event);
     fn is_pending(&self, ev: Event, edge: SignalEdge) -> bool;
     fn unpend(&self, ev: Event);
+    /// Returns the EXTICR index of the port driving the GPIO line `ev` if the line is
+    /// claimed or unmasked as an interrupt or an event, `None` for a free or non-GPIO line
+    fn line_owner(&self, ev: Event) -> Option<u8>;
+    /// Routes the GPIO line `ev` to `port` (0 for GPIOA, 1 for GPIOB, ...) and claims it,
+    /// fails with `Error::LineInUse` if another port owns the line
+    ///
+    /// Claiming a line already owned by `port` succeeds. The claim is dropped by `unlisten`
+    /// and `release_line`.
+    fn claim_line(&self, ev: Event, port: u8) -> Result<(), Error>;
+    /// Drops the claim on the GPIO line `ev`, the interrupt and event masks are left as is
+    fn release_line(&self, ev: Event);
 }
 
 impl ExtiExt for EXTI {
@@ -100,6 +127,7 @@ impl ExtiExt for EXTI {
         self.imr1()
             .modify(|r, w| unsafe { w.bits(r.bits() & mask) });
         if line <= TRIGGER_MAX {
+            self.release_line(ev);
             self.rtsr1()
                 .modify(|r, w| unsafe { w.bits(r.bits() & mask) });
             self.ftsr1()
@@ -122,6 +150,68 @@ impl ExtiExt for EXTI {
         }
     }
 
+    fn line_owner(&self, ev: Event) -> Option<u8> {
+        let line = ev as u8;
+        if line > TRIGGER_MAX {
+            return None;
+        }
+        let mask = 1 << line;
+        let unmasked = self.imr1().read().bits() | self.emr1().read().bits();
+        if (u32::from(CLAIMED.load(Ordering::Relaxed)) | unmasked) & mask == 0 {
+            return None;
+        }
+        let exticr = match line {
+            0..=3 => self.exticr1().read().bits(),
+            4..=7 => self.exticr2().read().bits(),
+            8..=11 => self.exticr3().read().bits(),
+            _ => self.exticr4().read().bits(),
+        };
+        Some((exticr >> ((line % 4) * 8)) as u8)
+    }
+
+    fn claim_line(&self, ev: Event, port: u8) -> Result<(), Error> {
+        let line = ev as u8;
+        assert!(line <= TRIGGER_MAX);
+        interrupt::free(|_| {
+            match self.line_owner(ev) {
+                Some(owner) if owner != port => return Err(Error::LineInUse { port: owner }),
+                _ => {}
+            }
+
+            let offset = (line % 4) * 8;
+            let reset = !(0xff << offset);
+            let mask = u32::from(port) << offset;
+            match line {
+                0..=3 => self
+                    .exticr1()
+                    .modify(|r, w| unsafe { w.bits(r.bits() & reset | mask) }),
+                4..=7 => self
+                    .exticr2()
+                    .modify(|r, w| unsafe { w.bits(r.bits() & reset | mask) }),
+                8..=11 => self
+                    .exticr3()
+                    .modify(|r, w| unsafe { w.bits(r.bits() & reset | mask) }),
+                _ => self
+                    .exticr4()
+                    .modify(|r, w| unsafe { w.bits(r.bits() & reset | mask) }),
+            };
+            // The M0+ has no atomic read-modify-write, the critical section covers it
+            let claimed = CLAIMED.load(Ordering::Relaxed);
+            CLAIMED.store(claimed | 1 << line, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn release_line(&self, ev: Event) {
+        let line = ev as u8;
+        if line <= TRIGGER_MAX {
+            interrupt::free(|_| {
+                let claimed = CLAIMED.load(Ordering::Relaxed);
+                CLAIMED.store(claimed & !(1 << line), Ordering::Relaxed);
+            });
+        }
+    }
+
     fn unpend(&self, ev: Event) {
         let line = ev as u8;
         if line <= TRIGGER_MAX {
@@ -0,0 +1,193 @@
+//! Flash memory programming
+//!
+//! The main flash is erased by 2 KiB pages and programmed by double words (64 bits), an
+//! erased double word reads as all ones and can only be programmed once.
+//!
+//! ```
+//! let mut flash = dp.FLASH.constrain();
+//! let mut unlocked = flash.unlock().unwrap();
+//! let page = hal::flash::FLASH_START + 15 * hal::flash::PAGE_SIZE;
+//! unlocked.erase_page(page).unwrap();
+//! unlocked.write(page, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+//! // the flash is locked again when `unlocked` is dropped
+//! ```
+use core::ptr;
+
+use crate::stm32::FLASH;
+
+/// Start address of the main flash
+pub const FLASH_START: u32 = 0x0800_0000;
+
+/// Size of an erase page in bytes
+pub const PAGE_SIZE: u32 = 2048;
+
+/// Size of a programming unit in bytes
+pub const DOUBLE_WORD: u32 = 8;
+
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+/// EOP and all the error flags of SR, cleared by writing ones
+const SR_FLAGS: u32 = 0xC3FB;
+
+/// Flash programming error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The unlock sequence did not unlock the flash, a wrong key locks it until the next reset
+    Locked,
+    /// The address is not page (erase) or double word (write) aligned
+    Unaligned,
+    /// The operation would reach outside the main flash
+    OutOfRange,
+    /// The page is write protected by the option bytes
+    WriteProtected,
+    /// The programming sequence failed, usually by writing a double word that is not erased
+    Programming,
+}
+
+/// Extension trait to constrain the FLASH peripheral
+pub trait FlashExt {
+    /// Constrains the FLASH peripheral
+    fn constrain(self) -> Flash;
+}
+
+impl FlashExt for FLASH {
+    fn constrain(self) -> Flash {
+        Flash { rb: self }
+    }
+}
+
+/// Constrained FLASH peripheral
+pub struct Flash {
+    rb: FLASH,
+}
+
+impl Flash {
+    /// Returns the size of the main flash in bytes, read from system memory
+    pub fn size(&self) -> u32 {
+        crate::signature::flash_size_kb() as u32 * 1024
+    }
+
+    /// Unlocks the flash for erasing and programming, it is locked again when the returned
+    /// [`UnlockedFlash`] is dropped
+    pub fn unlock(&mut self) -> Result<UnlockedFlash<'_>, Error> {
+        if self.rb.cr().read().lock().bit_is_set() {
+            self.rb.keyr().write(|w| unsafe { w.key().bits(KEY1) });
+            self.rb.keyr().write(|w| unsafe { w.key().bits(KEY2) });
+        }
+        if self.rb.cr().read().lock().bit_is_set() {
+            return Err(Error::Locked);
+        }
+        Ok(UnlockedFlash { flash: self })
+    }
+
+    pub fn release(self) -> FLASH {
+        self.rb
+    }
+}
+
+/// Unlocked flash, locks it back on drop
+pub struct UnlockedFlash<'a> {
+    flash: &'a mut Flash,
+}
+
+impl UnlockedFlash<'_> {
+    /// Erases the page starting at `address`
+    pub fn erase_page(&mut self, address: u32) -> Result<(), Error> {
+        if address % PAGE_SIZE != 0 {
+            return Err(Error::Unaligned);
+        }
+        self.check_range(address, PAGE_SIZE)?;
+
+        let page = ((address - FLASH_START) / PAGE_SIZE) as u8;
+        self.wait_idle();
+        self.clear_flags();
+        let rb = &self.flash.rb;
+        rb.cr()
+            .modify(|_, w| unsafe { w.per().set_bit().pnb().bits(page) });
+        rb.cr().modify(|_, w| w.strt().set_bit());
+        self.wait_idle();
+        self.flash.rb.cr().modify(|_, w| w.per().clear_bit());
+        self.check_errors()
+    }
+
+    /// Programs `data` at `address`, which must be double word aligned
+    ///
+    /// A trailing partial double word is padded with `0xff`, the flash must have been erased
+    /// beforehand.
+    pub fn write(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        if address % DOUBLE_WORD != 0 {
+            return Err(Error::Unaligned);
+        }
+        self.check_range(address, data.len() as u32)?;
+
+        self.wait_idle();
+        self.clear_flags();
+        self.flash.rb.cr().modify(|_, w| w.pg().set_bit());
+
+        let mut res = Ok(());
+        for (n, chunk) in data.chunks(DOUBLE_WORD as usize).enumerate() {
+            let mut buf = [0xff; DOUBLE_WORD as usize];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            let low = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+            let high = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+
+            let dst = (address + n as u32 * DOUBLE_WORD) as *mut u32;
+            unsafe {
+                // the second word write starts the programming of the double word
+                ptr::write_volatile(dst, low);
+                ptr::write_volatile(dst.add(1), high);
+            }
+            self.wait_idle();
+            res = self.check_errors();
+            if res.is_err() {
+                break;
+            }
+        }
+
+        self.flash.rb.cr().modify(|_, w| w.pg().clear_bit());
+        res
+    }
+
+    fn check_range(&self, address: u32, len: u32) -> Result<(), Error> {
+        let end = FLASH_START + self.flash.size();
+        if address < FLASH_START || address.checked_add(len).map_or(true, |last| last > end) {
+            Err(Error::OutOfRange)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Waits for BSY1 and CFGBSY, PG/PER must stay set and no operation may start until both
+    /// are clear
+    fn wait_idle(&self) {
+        while {
+            let sr = self.flash.rb.sr().read();
+            sr.bsy1().bit_is_set() || sr.cfgbsy().bit_is_set()
+        } {}
+    }
+
+    fn clear_flags(&mut self) {
+        self.flash.rb.sr().write(|w| unsafe { w.bits(SR_FLAGS) });
+    }
+
+    fn check_errors(&mut self) -> Result<(), Error> {
+        let sr = self.flash.rb.sr().read();
+        let res = if sr.wrperr().bit_is_set() {
+            Err(Error::WriteProtected)
+        } else if sr.bits() & SR_FLAGS & !1 != 0 {
+            Err(Error::Programming)
+        } else {
+            Ok(())
+        };
+        self.clear_flags();
+        res
+    }
+}
+
+impl Drop for UnlockedFlash<'_> {
+    fn drop(&mut self) {
+        self.wait_idle();
+        self.flash.rb.cr().modify(|_, w| w.lock().set_bit());
+    }
+}
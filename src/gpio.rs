@@ -4,7 +4,11 @@ use core::marker::PhantomData;
 use crate::rcc::Rcc;
 use core::convert::Infallible;
 use embedded_hal::digital::v2::PinState;
-use hal::digital::v2::{toggleable, InputPin, OutputPin, StatefulOutputPin};
+use hal::digital::v2::{toggleable, InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
+
+mod debounce;
+
+pub use debounce::*;
 
 /// Default pin mode
 pub type DefaultMode = Analog;
@@ -23,6 +27,7 @@ trait GpioRegExt {
     fn is_set_low(&self, pos: u8) -> bool;
     fn set_high(&self, pos: u8);
     fn set_low(&self, pos: u8);
+    fn set_mode(&self, pos: u8, mode: Dynamic);
 }
 
 /// Input mode (type state)
@@ -53,7 +58,7 @@ pub struct Output<MODE> {
 /// Push pull output (type state)
 pub struct PushPull;
 
-/// Fully erased pin
+/// Fully erased pin, writes go through the BSRR of the pin's port
 pub struct Pin<MODE> {
     i: u8,
     port: *const dyn GpioRegExt,
@@ -82,12 +87,35 @@ macro_rules! gpio_trait {
                 // NOTE(unsafe) atomic write to a stateless register
                 unsafe { self.bsrr().write(|w| w.bits(1 << (pos + 16))) };
             }
+
+            fn set_mode(&self, pos: u8, mode: Dynamic) {
+                let offset = 2 * pos;
+                let (moder, pupdr, open_drain) = match mode {
+                    Dynamic::InputFloating => (0b00, 0b00, false),
+                    Dynamic::InputPullUp => (0b00, 0b01, false),
+                    Dynamic::InputPullDown => (0b00, 0b10, false),
+                    Dynamic::OutputPushPull => (0b01, 0b00, false),
+                    Dynamic::OutputOpenDrain => (0b01, 0b00, true),
+                };
+                unsafe {
+                    self.pupdr()
+                        .modify(|r, w| w.bits((r.bits() & !(0b11 << offset)) | (pupdr << offset)));
+                    self.otyper().modify(|r, w| {
+                        w.bits(r.bits() & !(0b1 << pos) | ((open_drain as u32) << pos))
+                    });
+                    self.moder()
+                        .modify(|r, w| w.bits((r.bits() & !(0b11 << offset)) | (moder << offset)));
+                }
+            }
         }
     };
 }
 
 gpio_trait!(gpioa);
 gpio_trait!(gpiob);
+gpio_trait!(gpioc);
+gpio_trait!(gpiod);
+gpio_trait!(gpiof);
 
 // NOTE(unsafe) The only write acess is to BSRR, which is thread safe
 unsafe impl<MODE> Sync for Pin<MODE> {}
@@ -125,6 +153,39 @@ impl<MODE> OutputPin for Pin<Output<MODE>> {
 
 impl<MODE> toggleable::Default for Pin<Output<MODE>> {}
 
+impl<MODE> hal_1::digital::ErrorType for Pin<Output<MODE>> {
+    type Error = Infallible;
+}
+
+impl<MODE> hal_1::digital::OutputPin for Pin<Output<MODE>> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        OutputPin::set_low(self)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        OutputPin::set_high(self)
+    }
+}
+
+impl<MODE> hal_1::digital::StatefulOutputPin for Pin<Output<MODE>> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        StatefulOutputPin::is_set_high(self)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        StatefulOutputPin::is_set_low(self)
+    }
+}
+
+impl<MODE> Pin<Output<MODE>> {
+    /// Returns `true` if the pin actually reads high (IDR), unlike `is_set_high` which returns
+    /// the driven level (ODR)
+    #[inline(always)]
+    pub fn read_actual_level(&self) -> bool {
+        !unsafe { (*self.port).is_low(self.i) }
+    }
+}
+
 impl InputPin for Pin<Output<OpenDrain>> {
     type Error = Infallible;
 
@@ -153,6 +214,192 @@ impl<MODE> InputPin for Pin<Input<MODE>> {
     }
 }
 
+/// Mode of a [`DynamicPin`], tracked at runtime
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dynamic {
+    InputFloating,
+    InputPullUp,
+    InputPullDown,
+    OutputPushPull,
+    OutputOpenDrain,
+}
+
+/// Error returned when a [`DynamicPin`] is used in the wrong mode
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PinModeError {
+    IncorrectMode,
+}
+
+/// Fully erased pin whose mode can be switched at runtime
+pub struct DynamicPin {
+    i: u8,
+    port: *const dyn GpioRegExt,
+    mode: Dynamic,
+}
+
+// NOTE(unsafe) The only write acess is to BSRR, which is thread safe, mode changes need
+// `&mut self`
+unsafe impl Sync for DynamicPin {}
+unsafe impl Send for DynamicPin {}
+
+impl DynamicPin {
+    /// Returns the current mode
+    pub fn mode(&self) -> Dynamic {
+        self.mode
+    }
+
+    pub fn make_floating_input(&mut self) {
+        self.set_mode(Dynamic::InputFloating);
+    }
+
+    pub fn make_pull_up_input(&mut self) {
+        self.set_mode(Dynamic::InputPullUp);
+    }
+
+    pub fn make_pull_down_input(&mut self) {
+        self.set_mode(Dynamic::InputPullDown);
+    }
+
+    pub fn make_push_pull_output(&mut self) {
+        self.set_mode(Dynamic::OutputPushPull);
+    }
+
+    pub fn make_open_drain_output(&mut self) {
+        self.set_mode(Dynamic::OutputOpenDrain);
+    }
+
+    fn set_mode(&mut self, mode: Dynamic) {
+        unsafe { (*self.port).set_mode(self.i, mode) };
+        self.mode = mode;
+    }
+
+    fn is_output(&self) -> bool {
+        matches!(
+            self.mode,
+            Dynamic::OutputPushPull | Dynamic::OutputOpenDrain
+        )
+    }
+
+    /// Drives the pin high, fails unless the pin is an output
+    pub fn set_high(&mut self) -> Result<(), PinModeError> {
+        if !self.is_output() {
+            return Err(PinModeError::IncorrectMode);
+        }
+        unsafe { (*self.port).set_high(self.i) };
+        Ok(())
+    }
+
+    /// Drives the pin low, fails unless the pin is an output
+    pub fn set_low(&mut self) -> Result<(), PinModeError> {
+        if !self.is_output() {
+            return Err(PinModeError::IncorrectMode);
+        }
+        unsafe { (*self.port).set_low(self.i) };
+        Ok(())
+    }
+
+    /// Reads the pin level, fails if the pin is a push pull output
+    pub fn is_high(&self) -> Result<bool, PinModeError> {
+        self.is_low().map(|v| !v)
+    }
+
+    /// Reads the pin level, fails if the pin is a push pull output
+    pub fn is_low(&self) -> Result<bool, PinModeError> {
+        if self.mode == Dynamic::OutputPushPull {
+            return Err(PinModeError::IncorrectMode);
+        }
+        Ok(unsafe { (*self.port).is_low(self.i) })
+    }
+}
+
+impl hal_1::digital::Error for PinModeError {
+    fn kind(&self) -> hal_1::digital::ErrorKind {
+        hal_1::digital::ErrorKind::Other
+    }
+}
+
+impl hal_1::digital::ErrorType for DynamicPin {
+    type Error = PinModeError;
+}
+
+impl hal_1::digital::OutputPin for DynamicPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        DynamicPin::set_low(self)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        DynamicPin::set_high(self)
+    }
+}
+
+impl hal_1::digital::StatefulOutputPin for DynamicPin {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        self.is_set_low().map(|v| !v)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        if !self.is_output() {
+            return Err(PinModeError::IncorrectMode);
+        }
+        Ok(unsafe { (*self.port).is_set_low(self.i) })
+    }
+}
+
+/// Pin whose configuration is locked until the next reset, only reads and writes are exposed
+pub struct LockedPin<PIN> {
+    pin: PIN,
+}
+
+impl<PIN: OutputPin> OutputPin for LockedPin<PIN> {
+    type Error = PIN::Error;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_high()
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_low()
+    }
+}
+
+impl<PIN: StatefulOutputPin> StatefulOutputPin for LockedPin<PIN> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        self.pin.is_set_high()
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        self.pin.is_set_low()
+    }
+}
+
+impl<PIN: ToggleableOutputPin> ToggleableOutputPin for LockedPin<PIN> {
+    type Error = PIN::Error;
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        self.pin.toggle()
+    }
+}
+
+impl<PIN: InputPin> InputPin for LockedPin<PIN> {
+    type Error = PIN::Error;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.pin.is_high()
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.pin.is_low()
+    }
+}
+
+/// Internal pull resistor selection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Pull {
+    None = 0,
+    Up = 1,
+    Down = 2,
+}
+
 /// GPIO Pin speed selection
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Speed {
@@ -170,8 +417,9 @@ pub enum SignalEdge {
     All,
 }
 
-#[allow(dead_code)]
-pub(crate) enum AltFunction {
+/// Alternate function selection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AltFunction {
     AF0 = 0,
     AF1 = 1,
     AF2 = 2,
@@ -190,6 +438,29 @@ pub(crate) enum AltFunction {
     AF15 = 15,
 }
 
+impl AltFunction {
+    fn from_bits(bits: u32) -> AltFunction {
+        match bits & 0b1111 {
+            0 => AltFunction::AF0,
+            1 => AltFunction::AF1,
+            2 => AltFunction::AF2,
+            3 => AltFunction::AF3,
+            4 => AltFunction::AF4,
+            5 => AltFunction::AF5,
+            6 => AltFunction::AF6,
+            7 => AltFunction::AF7,
+            8 => AltFunction::AF8,
+            9 => AltFunction::AF9,
+            10 => AltFunction::AF10,
+            11 => AltFunction::AF11,
+            12 => AltFunction::AF12,
+            13 => AltFunction::AF13,
+            14 => AltFunction::AF14,
+            _ => AltFunction::AF15,
+        }
+    }
+}
+
 macro_rules! gpio {
     ($GPIOX:ident, $gpiox:ident, $PXx:ident, $Pxn:expr, [
         $($PXi:ident: ($pxi:ident, $i:expr),)+
@@ -198,7 +469,7 @@ macro_rules! gpio {
         pub mod $gpiox {
             use core::convert::Infallible;
             use core::marker::PhantomData;
-            use hal::digital::v2::{toggleable, InputPin, OutputPin, StatefulOutputPin};
+            use hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
             use crate::stm32::{EXTI, $GPIOX};
             use crate::exti::{ExtiExt, Event};
             use crate::rcc::{Enable, Rcc};
@@ -260,7 +531,57 @@ macro_rules! gpio {
                 }
             }
 
-            impl<MODE> toggleable::Default for $PXx<Output<MODE>> {
+            impl<MODE> $PXx<Output<MODE>> {
+                /// Toggles the pin with a single ODR read and BSRR write
+                fn internal_toggle(&mut self) {
+                    let gpio = unsafe { &(*$GPIOX::ptr()) };
+                    // NOTE(unsafe) atomic read with no side effects
+                    let bit = if gpio.odr().read().bits() & (1 << self.i) == 0 {
+                        1 << self.i
+                    } else {
+                        1 << (self.i + 16)
+                    };
+                    // NOTE(unsafe) atomic write to a stateless register
+                    unsafe { gpio.bsrr().write(|w| w.bits(bit)) };
+                }
+            }
+
+            impl<MODE> ToggleableOutputPin for $PXx<Output<MODE>> {
+                type Error = Infallible;
+
+                fn toggle(&mut self) -> Result<(), Self::Error> {
+                    self.internal_toggle();
+                    Ok(())
+                }
+            }
+
+            impl<MODE> hal_1::digital::ErrorType for $PXx<Output<MODE>> {
+                type Error = Infallible;
+            }
+
+            impl<MODE> hal_1::digital::OutputPin for $PXx<Output<MODE>> {
+                fn set_low(&mut self) -> Result<(), Self::Error> {
+                    OutputPin::set_low(self)
+                }
+
+                fn set_high(&mut self) -> Result<(), Self::Error> {
+                    OutputPin::set_high(self)
+                }
+            }
+
+            impl<MODE> hal_1::digital::StatefulOutputPin for $PXx<Output<MODE>> {
+                fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+                    StatefulOutputPin::is_set_high(self)
+                }
+
+                fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+                    StatefulOutputPin::is_set_low(self)
+                }
+
+                fn toggle(&mut self) -> Result<(), Self::Error> {
+                    self.internal_toggle();
+                    Ok(())
+                }
             }
 
             impl<MODE> InputPin for $PXx<Output<MODE>> {
@@ -447,7 +768,16 @@ macro_rules! gpio {
                     }
 
                     /// Configures the pin as external trigger
+                    ///
+                    /// # Panics
+                    ///
+                    /// Panics if the EXTI line is claimed by the same pin number of another port.
+                    /// Use `make_interrupt_source`, `trigger_on_edge` and `enable_interrupt` to
+                    /// get an error instead.
                     pub fn listen(self, edge: SignalEdge, exti: &mut EXTI) -> $PXi<Input<Floating>> {
+                        if let Err(err) = exti.claim_line(Event::from_code($i), $Pxn) {
+                            panic!("{:?}", err);
+                        }
                         let offset = 2 * $i;
                         unsafe {
                             let _ = &(*$GPIOX::ptr()).pupdr().modify(|r, w| {
@@ -457,37 +787,24 @@ macro_rules! gpio {
                                 w.bits(r.bits() & !(0b11 << offset))
                             });
                         };
-                        let offset = ($i % 4) * 8;
-                        let mask = $Pxn << offset;
-                        let reset = !(0xff << offset);
-                        match $i as u8 {
-                            0..=3   => exti.exticr1().modify(|r, w| unsafe {
-                                w.bits(r.bits() & reset | mask)
-                            }),
-                            4..=7  => exti.exticr2().modify(|r, w| unsafe {
-                                w.bits(r.bits() & reset | mask)
-                            }),
-                            8..=11 => exti.exticr3().modify(|r, w| unsafe {
-                                w.bits(r.bits() & reset | mask)
-                            }),
-                            12..=16 => exti.exticr4().modify(|r, w| unsafe {
-                                w.bits(r.bits() & reset | mask)
-                            }),
-                            _ => unreachable!(),
-                        };
                         exti.listen(Event::from_code($i), edge);
                         $PXi { _mode: PhantomData }
                     }
 
                     /// Set pin speed
                     pub fn set_speed(self, speed: Speed) -> Self {
+                        self.internal_set_speed(speed);
+                        self
+                    }
+
+                    #[allow(dead_code)]
+                    pub(crate) fn internal_set_speed(&self, speed: Speed) {
                         let offset = 2 * $i;
                         unsafe {
                             let _ = &(*$GPIOX::ptr()).ospeedr().modify(|r, w| {
                                 w.bits((r.bits() & !(0b11 << offset)) | ((speed as u32) << offset))
                             });
                         };
-                        self
                     }
 
                     #[allow(dead_code)]
@@ -513,6 +830,63 @@ macro_rules! gpio {
                         }
                     }
 
+                    /// Locks the pin configuration until the next reset
+                    ///
+                    /// LCKR is frozen by the first lock sequence on a port, so locking a second
+                    /// pin of the same port fails and gives the pin back.
+                    pub fn lock(self) -> Result<LockedPin<Self>, Self> {
+                        const LCKK: u32 = 1 << 16;
+                        let mask = 1 << $i;
+                        let gpio = unsafe { &(*$GPIOX::ptr()) };
+                        if gpio.lckr().read().bits() & LCKK != 0 {
+                            return Err(self);
+                        }
+                        unsafe {
+                            gpio.lckr().write(|w| w.bits(LCKK | mask));
+                            gpio.lckr().write(|w| w.bits(mask));
+                            gpio.lckr().write(|w| w.bits(LCKK | mask));
+                        }
+                        gpio.lckr().read();
+                        if gpio.lckr().read().bits() & LCKK == 0 {
+                            return Err(self);
+                        }
+                        Ok(LockedPin { pin: self })
+                    }
+
+                    /// Switches the pin mode in place, leaving the type state untouched
+                    #[allow(dead_code)]
+                    pub(crate) fn internal_set_mode(&self, mode: Dynamic) {
+                        unsafe { (*$GPIOX::ptr()).set_mode($i, mode) };
+                    }
+
+                    /// Turns the pin into a floating input whose mode can be switched at runtime
+                    pub fn into_dynamic(self) -> DynamicPin {
+                        self.into_floating_input();
+                        DynamicPin {
+                            i: $i,
+                            port: $GPIOX::ptr() as *const dyn GpioRegExt,
+                            mode: Dynamic::InputFloating,
+                        }
+                    }
+
+                    /// Returns the selected alternate function, or `None` if the pin isn't in
+                    /// alternate function mode
+                    pub fn alternate_function(&self) -> Option<AltFunction> {
+                        let offset = 2 * $i;
+                        let offset2 = 4 * $i;
+                        // NOTE(unsafe) atomic read with no side effects
+                        let gpio = unsafe { &(*$GPIOX::ptr()) };
+                        if (gpio.moder().read().bits() >> offset) & 0b11 != 0b10 {
+                            return None;
+                        }
+                        let bits = if offset2 < 32 {
+                            gpio.afrl().read().bits() >> offset2
+                        } else {
+                            gpio.afrh().read().bits() >> (offset2 - 32)
+                        };
+                        Some(AltFunction::from_bits(bits))
+                    }
+
                     fn internal_set_state(&mut self, state: PinState) {
                         match state {
                             PinState::High => {
@@ -527,6 +901,21 @@ macro_rules! gpio {
                     }
                 }
 
+                impl $PXi<Output<OpenDrain>> {
+                    /// Enables or disables the internal pull-up resistor
+                    pub fn internal_pull_up(self, on: bool) -> Self {
+                        let offset = 2 * $i;
+                        let value = if on { 0b01 } else { 0b00 };
+                        unsafe {
+                            let gpio = &(*$GPIOX::ptr());
+                            gpio.pupdr().modify(|r, w| {
+                                w.bits((r.bits() & !(0b11 << offset)) | (value << offset))
+                            });
+                        };
+                        self
+                    }
+                }
+
                 impl<MODE> $PXi<Output<MODE>> {
                     /// Erases the pin number from the type
                     ///
@@ -535,6 +924,21 @@ macro_rules! gpio {
                     pub fn downgrade(self) -> $PXx<Output<MODE>> {
                         $PXx { i: $i, _mode: self._mode }
                     }
+
+                    /// Erases the pin and port numbers from the type
+                    pub fn erase(self) -> Pin<Output<MODE>> {
+                        self.downgrade().downgrade()
+                    }
+
+                    /// Returns `true` if the pin actually reads high (IDR), unlike
+                    /// `is_set_high` which returns the driven level (ODR)
+                    ///
+                    /// A mismatch with `is_set_high` means the line is held by something else,
+                    /// e.g. shorted to ground.
+                    pub fn read_actual_level(&self) -> bool {
+                        // NOTE(unsafe) atomic read with no side effects
+                        unsafe { (*$GPIOX::ptr()).idr().read().bits() & (1 << $i) != 0 }
+                    }
                 }
 
                 impl<MODE> OutputPin for $PXi<Output<MODE>> {
@@ -564,7 +968,59 @@ macro_rules! gpio {
                     }
                 }
 
-                impl<MODE> toggleable::Default for $PXi<Output<MODE>> {
+                impl<MODE> $PXi<Output<MODE>> {
+                    /// Toggles the pin with a single ODR read and BSRR write
+                    fn internal_toggle(&mut self) {
+                        let gpio = unsafe { &(*$GPIOX::ptr()) };
+                        // NOTE(unsafe) atomic read with no side effects
+                        let bit = if gpio.odr().read().bits() & (1 << $i) == 0 {
+                            1 << $i
+                        } else {
+                            1 << ($i + 16)
+                        };
+                        // NOTE(unsafe) atomic write to a stateless register
+                        unsafe { gpio.bsrr().write(|w| w.bits(bit)) };
+                    }
+                }
+
+                impl<MODE> ToggleableOutputPin for $PXi<Output<MODE>> {
+                    type Error = Infallible;
+
+                    fn toggle(&mut self) -> Result<(), Self::Error> {
+                        self.internal_toggle();
+                        Ok(())
+                    }
+                }
+
+                impl<MODE> hal_1::digital::ErrorType for $PXi<Output<MODE>> {
+                    type Error = Infallible;
+                }
+
+                impl<MODE> hal_1::digital::OutputPin for $PXi<Output<MODE>> {
+                    fn set_low(&mut self) -> Result<(), Self::Error> {
+                        self.internal_set_state(PinState::Low);
+                        Ok(())
+                    }
+
+                    fn set_high(&mut self) -> Result<(), Self::Error> {
+                        self.internal_set_state(PinState::High);
+                        Ok(())
+                    }
+                }
+
+                impl<MODE> hal_1::digital::StatefulOutputPin for $PXi<Output<MODE>> {
+                    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+                        StatefulOutputPin::is_set_high(self)
+                    }
+
+                    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+                        StatefulOutputPin::is_set_low(self)
+                    }
+
+                    fn toggle(&mut self) -> Result<(), Self::Error> {
+                        self.internal_toggle();
+                        Ok(())
+                    }
                 }
 
                 impl<MODE> InputPin for $PXi<Output<MODE>> {
@@ -590,6 +1046,90 @@ macro_rules! gpio {
                     pub fn downgrade(self) -> $PXx<Input<MODE>> {
                         $PXx { i: $i, _mode: self._mode }
                     }
+
+                    /// Erases the pin and port numbers from the type
+                    pub fn erase(self) -> Pin<Input<MODE>> {
+                        self.downgrade().downgrade()
+                    }
+                }
+
+                impl<MODE> $PXi<Input<MODE>> {
+                    /// Rewrites the pull resistor configuration in place
+                    ///
+                    /// The `MODE` type state keeps naming the pull selected when the pin was
+                    /// configured.
+                    pub fn set_internal_resistor(&mut self, pull: Pull) {
+                        let offset = 2 * $i;
+                        unsafe {
+                            let gpio = &(*$GPIOX::ptr());
+                            gpio.pupdr().modify(|r, w| {
+                                w.bits((r.bits() & !(0b11 << offset)) | ((pull as u32) << offset))
+                            });
+                        };
+                    }
+
+                    /// Routes this pin to its EXTI line and claims it
+                    ///
+                    /// EXTI line `n` is shared by pin `n` of every port, this fails with
+                    /// `Error::LineInUse` while the same pin number of another port holds the
+                    /// line. The claim is dropped by `disable_interrupt`.
+                    pub fn make_interrupt_source(
+                        &mut self,
+                        exti: &mut EXTI,
+                    ) -> Result<(), crate::exti::Error> {
+                        exti.claim_line(Event::from_code($i), $Pxn)
+                    }
+
+                    /// Selects the edge(s) triggering the EXTI line
+                    pub fn trigger_on_edge(&mut self, exti: &mut EXTI, edge: SignalEdge) {
+                        let mask = 1 << $i;
+                        let (rising, falling) = match edge {
+                            SignalEdge::Rising => (true, false),
+                            SignalEdge::Falling => (false, true),
+                            SignalEdge::All => (true, true),
+                        };
+                        exti.rtsr1().modify(|r, w| unsafe {
+                            w.bits(if rising { r.bits() | mask } else { r.bits() & !mask })
+                        });
+                        exti.ftsr1().modify(|r, w| unsafe {
+                            w.bits(if falling { r.bits() | mask } else { r.bits() & !mask })
+                        });
+                    }
+
+                    /// Unmasks the EXTI line interrupt, claiming the line like
+                    /// `make_interrupt_source` first
+                    pub fn enable_interrupt(
+                        &mut self,
+                        exti: &mut EXTI,
+                    ) -> Result<(), crate::exti::Error> {
+                        exti.claim_line(Event::from_code($i), $Pxn)?;
+                        exti.wakeup(Event::from_code($i));
+                        Ok(())
+                    }
+
+                    /// Masks the EXTI line interrupt and drops the claim on the line
+                    pub fn disable_interrupt(&mut self, exti: &mut EXTI) {
+                        exti.imr1().modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $i)) });
+                        exti.release_line(Event::from_code($i));
+                    }
+
+                    /// Clears the rising and falling pending bits of the EXTI line
+                    pub fn clear_interrupt_pending_bit(&mut self) {
+                        // NOTE(unsafe) write-1-to-clear of the line owned by this pin
+                        unsafe {
+                            let exti = &(*EXTI::ptr());
+                            exti.rpr1().write(|w| w.bits(1 << $i));
+                            exti.fpr1().write(|w| w.bits(1 << $i));
+                        }
+                    }
+
+                    /// Returns `true` if the EXTI line has a pending rising or falling edge
+                    pub fn check_interrupt(&self) -> bool {
+                        // NOTE(unsafe) atomic read with no side effects
+                        let exti = unsafe { &(*EXTI::ptr()) };
+                        let mask = 1 << $i;
+                        (exti.rpr1().read().bits() | exti.fpr1().read().bits()) & mask != 0
+                    }
                 }
 
                 impl<MODE> InputPin for $PXi<Input<MODE>> {
@@ -614,33 +1154,80 @@ macro_rules! gpio {
                 }
             }
 
-            // impl<MODE> $PXx<Output<MODE>> {
-            //     /// Erases the port number from the type
-            //     ///
-            //     /// This is useful when you want to collect the pins into an array where you
-            //     /// need all the elements to have the same type
-            //     pub fn downgrade(self) -> Pin<Output<MODE>> {
-            //         Pin {
-            //             i: self.get_id(),
-            //             port: $GPIOX::ptr() as *const dyn GpioRegExt,
-            //             _mode: self._mode,
-            //         }
-            //     }
-            // }
-
-            // impl<MODE> $PXx<Input<MODE>> {
-            //     /// Erases the port number from the type
-            //     ///
-            //     /// This is useful when you want to collect the pins into an array where you
-            //     /// need all the elements to have the same type
-            //     pub fn downgrade(self) -> Pin<Input<MODE>> {
-            //         Pin {
-            //             i: self.get_id(),
-            //             port: $GPIOX::ptr() as *const dyn GpioRegExt,
-            //             _mode: self._mode,
-            //         }
-            //     }
-            // }
+            /// Push pull outputs of this port written together with a single BSRR write
+            pub struct PortOutput<const MASK: u16> {
+                _0: (),
+            }
+
+            impl<const MASK: u16> PortOutput<MASK> {
+                /// Groups `pins`, the bits of `MASK` must be exactly their pin numbers
+                pub fn new<const N: usize>(pins: [$PXx<Output<PushPull>>; N]) -> Self {
+                    let mask = pins.iter().fold(0u16, |mask, pin| mask | 1 << pin.i);
+                    assert!(mask == MASK && MASK.count_ones() as usize == N);
+                    PortOutput { _0: () }
+                }
+
+                /// Drives every pin of the group from the matching bit of `value`
+                pub fn write(&mut self, value: u16) {
+                    self.write_port(MASK, value);
+                }
+
+                /// Drives the pins of the group selected by `mask` from the matching bits of
+                /// `value`, the other pins keep their state
+                pub fn write_port(&mut self, mask: u16, value: u16) {
+                    let mask = (mask & MASK) as u32;
+                    let value = value as u32;
+                    let bits = (value & mask) | ((!value & mask) << 16);
+                    // NOTE(unsafe) atomic write to a stateless register
+                    unsafe { (*$GPIOX::ptr()).bsrr().write(|w| w.bits(bits)) };
+                }
+
+                /// Splits the group back into its pins, ordered by pin number
+                pub fn release<const N: usize>(self) -> [$PXx<Output<PushPull>>; N] {
+                    assert!(MASK.count_ones() as usize == N);
+                    let mut bits = MASK;
+                    core::array::from_fn(|_| {
+                        let i = bits.trailing_zeros() as u8;
+                        bits &= bits - 1;
+                        $PXx { i, _mode: PhantomData }
+                    })
+                }
+            }
+
+            impl<MODE> $PXx<Output<MODE>> {
+                /// Erases the port number from the type
+                ///
+                /// This is useful when you want to collect the pins into an array where you
+                /// need all the elements to have the same type
+                pub fn downgrade(self) -> Pin<Output<MODE>> {
+                    Pin {
+                        i: self.get_id(),
+                        port: $GPIOX::ptr() as *const dyn GpioRegExt,
+                        _mode: self._mode,
+                    }
+                }
+
+                /// Returns `true` if the pin actually reads high (IDR), unlike `is_set_high`
+                /// which returns the driven level (ODR)
+                pub fn read_actual_level(&self) -> bool {
+                    // NOTE(unsafe) atomic read with no side effects
+                    unsafe { (*$GPIOX::ptr()).idr().read().bits() & (1 << self.i) != 0 }
+                }
+            }
+
+            impl<MODE> $PXx<Input<MODE>> {
+                /// Erases the port number from the type
+                ///
+                /// This is useful when you want to collect the pins into an array where you
+                /// need all the elements to have the same type
+                pub fn downgrade(self) -> Pin<Input<MODE>> {
+                    Pin {
+                        i: self.get_id(),
+                        port: $GPIOX::ptr() as *const dyn GpioRegExt,
+                        _mode: self._mode,
+                    }
+                }
+            }
         }
 
         pub use $gpiox::{ $($PXi,)+ };
@@ -0,0 +1,90 @@
+//! Input debouncing driven by a periodic timer
+use hal::digital::v2::InputPin;
+use hal::timer::{CountDown, Periodic};
+
+use super::SignalEdge;
+
+/// Input pin sampled on every timer update, a level change is only reported after `samples`
+/// consecutive samples at the new level
+///
+/// ```
+/// let timer = dp.TIM14.timer(&mut rcc);
+/// let mut button = DebouncedInput::new(gpioa.pa5.into_pull_up_input(), timer, 1.millis(), 10);
+/// loop {
+///     if let Some(SignalEdge::Falling) = button.poll() {
+///         // pressed for at least 10 ms
+///     }
+/// }
+/// ```
+pub struct DebouncedInput<PIN, TIM> {
+    pin: PIN,
+    timer: TIM,
+    samples: u8,
+    count: u8,
+    high: bool,
+}
+
+impl<PIN, TIM> DebouncedInput<PIN, TIM>
+where
+    PIN: InputPin,
+    TIM: CountDown + Periodic,
+{
+    /// Starts `timer` with the sample `period`, the initial stable level is the current pin
+    /// level
+    ///
+    /// `samples` is clamped to at least 1.
+    pub fn new<T>(pin: PIN, mut timer: TIM, period: T, samples: u8) -> Self
+    where
+        T: Into<TIM::Time>,
+    {
+        let high = pin.is_high().unwrap_or(false);
+        timer.start(period);
+        DebouncedInput {
+            pin,
+            timer,
+            samples: samples.max(1),
+            count: 0,
+            high,
+        }
+    }
+
+    /// Samples the pin if the timer has updated since the last call
+    ///
+    /// Returns `SignalEdge::Rising` or `SignalEdge::Falling` once the new level has been seen
+    /// for `samples` consecutive samples, a glitch shorter than that resets the count.
+    pub fn poll(&mut self) -> Option<SignalEdge> {
+        self.timer.wait().ok()?;
+        let high = self.pin.is_high().ok()?;
+        if high == self.high {
+            self.count = 0;
+            return None;
+        }
+
+        self.count += 1;
+        if self.count < self.samples {
+            return None;
+        }
+        self.count = 0;
+        self.high = high;
+        Some(if high {
+            SignalEdge::Rising
+        } else {
+            SignalEdge::Falling
+        })
+    }
+
+    /// Returns true if the debounced level is high
+    pub fn is_high(&self) -> bool {
+        self.high
+    }
+
+    /// Returns true if the debounced level is low
+    pub fn is_low(&self) -> bool {
+        !self.high
+    }
+
+    /// Releases the pin and the timer
+    pub fn release(self) -> (PIN, TIM) {
+        (self.pin, self.timer)
+    }
+}
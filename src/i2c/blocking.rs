@@ -1,10 +1,15 @@
 //! I2C
+use crate::dma::{self, Channel, Direction, DmaMuxIndex, WordSize};
 use crate::gpio::*;
 use crate::i2c::config::Config;
-use crate::i2c::{self, Error, I2c, I2cDirection, I2cExt, SCLPin, SDAPin};
+use crate::i2c::{self, Address, Error, I2c, I2cDirection, I2cExt, SCLPin, SDAPin};
 use crate::rcc::*;
+use crate::stm32::i2c1::{isr, RegisterBlock as I2cRB};
 use crate::stm32::I2C1;
+use core::cmp;
+use core::ops::Deref;
 use hal::blocking::i2c::{Read, Write, WriteRead};
+use hal_1::i2c::{Operation, SevenBitAddress};
 
 #[cfg(feature = "stm32c071")]
 use crate::stm32::I2C2;
@@ -63,12 +68,20 @@ macro_rules! flush_rxdr {
 /// In case of hard errors the error will be returned, also forcing the caller of this function to return
 /// In all other case the macro will return without a result
 macro_rules! busy_wait {
-    ($i2c:expr, $flag:ident, $variant:ident, $idx:ident, $buflen:ident) => {
+    ($i2c:expr, $flag:ident, $variant:ident, $idx:ident, $buflen:ident, $timeout:expr) => {
+        let timeout: u32 = $timeout;
+        let mut polls: u32 = 0;
         loop {
             let isr = $i2c.isr().read();
 
             if isr.$flag().$variant() {
                 break
+            } else if timeout != 0 && polls >= timeout {
+                reset(&$i2c);
+                return Err(Error::Timeout);
+            } else if isr.timeout().bit_is_set() {
+                $i2c.icr().write(|w| w.timoutcf().bit(true));
+                return Err(Error::Timeout);
             } else  if isr.berr().bit_is_set() {
                 $i2c.icr().write(|w| w.berrcf().bit(true));
                 return Err(Error::BusError);
@@ -108,13 +121,389 @@ macro_rules! busy_wait {
                 }
             } else  {
                 // try again
+                polls += 1;
             }
         }
     };
 }
 
+/// Resets the I2C state machine and releases the lines, the configuration is kept
+fn reset(i2c: &I2cRB) {
+    i2c.cr1().modify(|_, w| w.pe().clear_bit());
+    // PE must stay low for at least three APB cycles
+    while i2c.cr1().read().pe().bit_is_set() {}
+    cortex_m::asm::delay(3);
+    i2c.cr1().modify(|_, w| w.pe().set_bit());
+}
+
+/// Waits for `flag` in ISR, failing on a bus error, an arbitration loss, a NACK or once
+/// `timeout` polls elapsed (0 waits forever)
+fn wait_for(i2c: &I2cRB, timeout: u32, flag: impl Fn(&isr::R) -> bool) -> Result<(), Error> {
+    let mut polls: u32 = 0;
+    loop {
+        let isr = i2c.isr().read();
+        if timeout != 0 && polls >= timeout {
+            reset(i2c);
+            return Err(Error::Timeout);
+        }
+        polls += 1;
+        if isr.timeout().bit_is_set() {
+            i2c.icr().write(|w| w.timoutcf().bit(true));
+            return Err(Error::Timeout);
+        } else if isr.berr().bit_is_set() {
+            i2c.icr().write(|w| w.berrcf().bit(true));
+            return Err(Error::BusError);
+        } else if isr.arlo().bit_is_set() {
+            i2c.icr().write(|w| w.arlocf().bit(true));
+            return Err(Error::ArbitrationLost);
+        } else if isr.nackf().bit_is_set() {
+            // The master generates the STOP condition by itself after a NACK
+            i2c.icr().write(|w| w.nackcf().bit(true));
+            while i2c.isr().read().stopf().bit_is_clear() {
+                if timeout != 0 && polls >= timeout {
+                    reset(i2c);
+                    return Err(Error::Timeout);
+                }
+                polls += 1;
+            }
+            i2c.icr().write(|w| w.stopcf().bit(true));
+            flush_txdr!(i2c);
+            return Err(Error::Nack);
+        } else if flag(&isr) {
+            return Ok(());
+        }
+    }
+}
+
+/// Waits for a pending START request to be sent, resets the peripheral, which drops the
+/// request, once `timeout` polls elapsed (0 waits forever)
+fn wait_start(i2c: &I2cRB, timeout: u32) -> Result<(), Error> {
+    let mut polls: u32 = 0;
+    while i2c.cr2().read().start().bit_is_set() {
+        if timeout != 0 && polls >= timeout {
+            reset(i2c);
+            return Err(Error::Timeout);
+        }
+        polls += 1;
+    }
+    Ok(())
+}
+
+/// Waits for and clears the STOP condition flag, resets the peripheral once `timeout` polls
+/// elapsed (0 waits forever)
+fn wait_stop(i2c: &I2cRB, timeout: u32) -> Result<(), Error> {
+    let mut polls: u32 = 0;
+    while i2c.isr().read().stopf().bit_is_clear() {
+        if timeout != 0 && polls >= timeout {
+            reset(i2c);
+            return Err(Error::Timeout);
+        }
+        polls += 1;
+    }
+    i2c.icr().write(|w| w.stopcf().bit(true));
+    Ok(())
+}
+
+/// Returns the SADD bits and the ADD10 flag for `addr`
+fn slave_address(addr: Address) -> (u16, bool) {
+    match addr {
+        Address::SevenBit(addr) => {
+            assert!(addr < 0x80);
+            ((addr as u16) << 1, false)
+        }
+        Address::TenBit(addr) => {
+            assert!(addr < 0x400);
+            (addr, true)
+        }
+    }
+}
+
+/// Runs an embedded-hal 1.0 transaction as a master
+///
+/// Adjacent operations of the same direction are merged into one transfer using RELOAD, so
+/// only a direction change emits a repeated START. Buffers longer than 255 bytes are sent in
+/// several NBYTES chunks.
+fn transaction(
+    i2c: &I2cRB,
+    timeout: u32,
+    addr: Address,
+    operations: &mut [Operation<'_>],
+) -> Result<(), Error> {
+    let (sadd, add10) = slave_address(addr);
+
+    // Wait for any previous address sequence to end automatically
+    wait_start(i2c, timeout)?;
+    // Another master or a stuck slave keeps the bus busy
+    wait_for(i2c, timeout, |isr| isr.busy().bit_is_clear())?;
+    flush_txdr!(i2c);
+    flush_rxdr!(i2c);
+
+    let is_read = |op: &Operation<'_>| matches!(op, Operation::Read(_));
+    let op_len = |op: &Operation<'_>| match op {
+        Operation::Read(buf) => buf.len(),
+        Operation::Write(buf) => buf.len(),
+    };
+
+    let mut prev_read = None;
+    for i in 0..operations.len() {
+        let read = is_read(&operations[i]);
+        let len = op_len(&operations[i]);
+        let merged = prev_read == Some(read);
+        if merged && len == 0 {
+            continue;
+        }
+        // The next non-empty operation decides whether the transfer carries on after this one
+        let next_same = operations[i + 1..]
+            .iter()
+            .find(|op| op_len(op) > 0 || is_read(op) != read)
+            .map_or(false, |op| is_read(op) == read);
+
+        let mut offset = 0;
+        let mut start = !merged;
+        loop {
+            let chunk = cmp::min(len - offset, 255);
+            let reload = offset + chunk < len || next_same;
+            if start {
+                if prev_read.is_some() {
+                    wait_for(i2c, timeout, |isr| isr.tc().bit_is_set())?;
+                }
+                i2c.cr2().write(|w| unsafe {
+                    w.sadd()
+                        .bits(sadd)
+                        .add10()
+                        .bit(add10)
+                        .head10r()
+                        .clear_bit()
+                        .rd_wrn()
+                        .bit(read)
+                        .nbytes()
+                        .bits(chunk as u8)
+                        .reload()
+                        .bit(reload)
+                        .autoend()
+                        .clear_bit()
+                        .start()
+                        .set_bit()
+                });
+                start = false;
+            } else {
+                wait_for(i2c, timeout, |isr| isr.tcr().bit_is_set())?;
+                i2c.cr2()
+                    .modify(|_, w| unsafe { w.nbytes().bits(chunk as u8).reload().bit(reload) });
+            }
+
+            match &mut operations[i] {
+                Operation::Read(buf) => {
+                    for byte in &mut buf[offset..offset + chunk] {
+                        wait_for(i2c, timeout, |isr| isr.rxne().bit_is_set())?;
+                        *byte = i2c.rxdr().read().rxdata().bits();
+                    }
+                }
+                Operation::Write(buf) => {
+                    for byte in &buf[offset..offset + chunk] {
+                        wait_for(i2c, timeout, |isr| isr.txis().bit_is_set())?;
+                        i2c.txdr().write(|w| unsafe { w.txdata().bits(*byte) });
+                    }
+                }
+            }
+
+            offset += chunk;
+            if offset >= len {
+                break;
+            }
+        }
+        prev_read = Some(read);
+    }
+
+    if prev_read.is_some() {
+        wait_for(i2c, timeout, |isr| isr.tc().bit_is_set())?;
+        i2c.cr2().modify(|_, w| w.stop().set_bit());
+        wait_for(i2c, timeout, |isr| isr.stopf().bit_is_set())?;
+        i2c.icr().write(|w| w.stopcf().bit(true));
+    }
+    Ok(())
+}
+
+/// A DMA transfer in progress
+///
+/// The DMA channel moves the data while [`Transfer::poll`] loads the next NBYTES chunk on each
+/// TCR event, so transfers longer than 255 bytes need `poll` (or `wait`) to be called until
+/// they complete. The STOP condition is sent once the last byte went through.
+pub struct Transfer<I2C, CH, BUF> {
+    i2c: I2C,
+    channel: CH,
+    buffer: BUF,
+    address: (u16, bool),
+    /// Bytes of the current direction not covered by NBYTES yet
+    remaining: usize,
+    /// Request line, buffer address and length of the read following a write
+    read: Option<(DmaMuxIndex, u32, usize)>,
+    done: bool,
+}
+
+/// Sets `channel` up for a byte transfer of `len` items between `peripheral` and `memory`
+fn setup_channel<CH: Channel>(
+    channel: &mut CH,
+    request: DmaMuxIndex,
+    direction: Direction,
+    peripheral: u32,
+    memory: u32,
+    len: usize,
+) {
+    let config = dma::Config::default().request(request);
+    dma::setup_channel(
+        channel,
+        peripheral,
+        memory,
+        len,
+        WordSize::BITS8,
+        direction,
+        config,
+    );
+}
+
+/// Sends a (repeated) START for a `len` bytes transfer, returns the bytes left for RELOAD
+fn start_dma(i2c: &I2cRB, (sadd, add10): (u16, bool), read: bool, len: usize) -> usize {
+    let chunk = cmp::min(len, 255);
+    i2c.cr2().write(|w| unsafe {
+        w.sadd()
+            .bits(sadd)
+            .add10()
+            .bit(add10)
+            .head10r()
+            .clear_bit()
+            .rd_wrn()
+            .bit(read)
+            .nbytes()
+            .bits(chunk as u8)
+            .reload()
+            .bit(len > 255)
+            .autoend()
+            .clear_bit()
+            .start()
+            .set_bit()
+    });
+    len - chunk
+}
+
+impl<I2C, SDA, SCL, CH, BUF> Transfer<I2c<I2C, SDA, SCL>, CH, BUF>
+where
+    I2C: Deref<Target = I2cRB>,
+    CH: Channel,
+{
+    /// Advances the transfer, returns `Ok` once the STOP condition has been sent
+    ///
+    /// A NACK, a bus error, an arbitration loss, a bus timeout or a DMA transfer error ends
+    /// the transfer with the error, the DMA channel is stopped in every case. Waiting for the
+    /// STOP condition is bounded by the timeout set with [`Config::timeout`].
+    pub fn poll(&mut self) -> nb::Result<(), Error> {
+        if self.done {
+            return Ok(());
+        }
+
+        let i2c: &I2cRB = &self.i2c.i2c;
+        let timeout = self.i2c.timeout;
+        let isr = i2c.isr().read();
+        let err = if isr.berr().bit_is_set() {
+            i2c.icr().write(|w| w.berrcf().bit(true));
+            Some(Error::BusError)
+        } else if isr.arlo().bit_is_set() {
+            i2c.icr().write(|w| w.arlocf().bit(true));
+            Some(Error::ArbitrationLost)
+        } else if isr.timeout().bit_is_set() {
+            i2c.icr().write(|w| w.timoutcf().bit(true));
+            Some(Error::Timeout)
+        } else if self.channel.is_error() {
+            // The bus is left mid-transfer, reset it like an aborted `release`
+            reset(i2c);
+            Some(Error::Dma)
+        } else if isr.nackf().bit_is_set() {
+            // The master generates the STOP condition by itself after a NACK
+            i2c.icr().write(|w| w.nackcf().bit(true));
+            match wait_stop(i2c, timeout) {
+                Ok(()) => {
+                    flush_txdr!(i2c);
+                    Some(Error::Nack)
+                }
+                Err(err) => Some(err),
+            }
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            self.finish();
+            return Err(nb::Error::Other(err));
+        }
+
+        if isr.tcr().bit_is_set() {
+            let chunk = cmp::min(self.remaining, 255);
+            self.remaining -= chunk;
+            i2c.cr2().modify(|_, w| unsafe {
+                w.nbytes()
+                    .bits(chunk as u8)
+                    .reload()
+                    .bit(self.remaining > 0)
+            });
+        } else if isr.tc().bit_is_set() && self.channel.is_complete() {
+            if let Some((request, buffer, len)) = self.read.take() {
+                self.channel.disable();
+                i2c.cr1().modify(|_, w| w.txdmaen().clear_bit());
+                let rxdr = i2c.rxdr().as_ptr() as u32;
+                setup_channel(
+                    &mut self.channel,
+                    request,
+                    Direction::FromPeripheral,
+                    rxdr,
+                    buffer,
+                    len,
+                );
+                i2c.cr1().modify(|_, w| w.rxdmaen().set_bit());
+                self.remaining = start_dma(i2c, self.address, true, len);
+            } else {
+                i2c.cr2().modify(|_, w| w.stop().set_bit());
+                let res = wait_stop(i2c, timeout);
+                self.finish();
+                return res.map_err(nb::Error::Other);
+            }
+        }
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Blocks until the transfer is over, see [`Self::poll()`]
+    pub fn wait(&mut self) -> Result<(), Error> {
+        nb::block!(self.poll())
+    }
+
+    /// Returns `true` once the transfer is over, successfully or not
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Gives back the I2C, the DMA channel and the buffer
+    ///
+    /// A transfer still in progress is aborted: the DMA channel is stopped before the buffer
+    /// is handed back and the I2C state machine is reset, which releases the lines.
+    pub fn release(mut self) -> (I2c<I2C, SDA, SCL>, CH, BUF) {
+        if !self.done {
+            self.finish();
+            reset(&self.i2c.i2c);
+        }
+        (self.i2c, self.channel, self.buffer)
+    }
+
+    fn finish(&mut self) {
+        self.channel.disable();
+        self.channel.clear_flags();
+        self.i2c
+            .i2c
+            .cr1()
+            .modify(|_, w| w.txdmaen().clear_bit().rxdmaen().clear_bit());
+        self.done = true;
+    }
+}
+
 macro_rules! i2c {
-    ($I2CX:ident, $i2cx:ident,
+    ($I2CX:ident, $i2cx:ident, $RX_REQ:ident, $TX_REQ:ident,
         sda: [ $(($PSDA:ty, $AFSDA:expr),)+ ],
         scl: [ $(($PSCL:ty, $AFSCL:expr),)+ ],
     ) => {
@@ -139,6 +528,17 @@ macro_rules! i2c {
                 fn release(self) -> Self {
                     self.into_open_drain_output()
                 }
+
+                fn clock_out(&mut self, pulses: u8, delay: &mut impl hal_1::delay::DelayNs) {
+                    self.internal_set_mode(Dynamic::OutputOpenDrain);
+                    for _ in 0..pulses {
+                        hal::digital::v2::OutputPin::set_low(self).ok();
+                        delay.delay_us(5);
+                        hal::digital::v2::OutputPin::set_high(self).ok();
+                        delay.delay_us(5);
+                    }
+                    SCLPin::<$I2CX>::setup(self);
+                }
             }
         )+
 
@@ -175,9 +575,18 @@ macro_rules! i2c {
                 i2c.cr1().modify(|_, w| w.pe().clear_bit());
 
                 // Setup protocol timings
-                let timing_bits = config.timing_bits(rcc.clocks.apb_clk);
+                let timing_bits = config.timing_bits(rcc.clocks.apb_clk());
                 i2c.timingr().write(|w| unsafe { w.bits(timing_bits) });
 
+                // TIMEOUTA is only writable while TIMOUTEN is cleared
+                i2c.timeoutr().reset();
+                if let Some(timeouta) = config.timeouta_bits(rcc.clocks.apb_clk()) {
+                    i2c.timeoutr().write(|w| unsafe {
+                        w.timeouta().bits(timeouta).tidle().clear_bit()
+                    });
+                    i2c.timeoutr().modify(|_, w| w.timouten().set_bit());
+                }
+
                 // Enable the I2C processing
                 i2c.cr1().modify(|_, w| unsafe {
                     w.pe()
@@ -212,7 +621,17 @@ macro_rules! i2c {
                 sda.setup();
                 scl.setup();
 
-                I2c { i2c, sda, scl }
+                I2c { i2c, sda, scl, timeout: config.timeout }
+            }
+
+            /// Frees a bus stuck by a slave holding SDA low
+            ///
+            /// The peripheral is disabled while SCL is clocked nine times as a GPIO, which lets
+            /// the slave finish the byte it is sending, and re-enabled afterwards.
+            pub fn recover_bus(&mut self, delay: &mut impl hal_1::delay::DelayNs) {
+                self.i2c.cr1().modify(|_, w| w.pe().clear_bit());
+                self.scl.clock_out(9, delay);
+                self.i2c.cr1().modify(|_, w| w.pe().set_bit());
             }
 
             pub fn listen(&mut self, ev: i2c::Event) {
@@ -236,11 +655,163 @@ macro_rules! i2c {
                 }
             }
 
+            /// Reads `buffer.len()` bytes from the slave at `address`
+            pub fn read_from(&mut self, address: Address, buffer: &mut [u8]) -> Result<(), Error> {
+                transaction(&self.i2c, self.timeout, address, &mut [Operation::Read(buffer)])
+            }
+
+            /// Writes `bytes` to the slave at `address`
+            pub fn write_to(&mut self, address: Address, bytes: &[u8]) -> Result<(), Error> {
+                transaction(&self.i2c, self.timeout, address, &mut [Operation::Write(bytes)])
+            }
+
+            /// Writes `bytes` then reads into `buffer` after a repeated START, `address` may be a
+            /// 7-bit or a 10-bit address
+            pub fn write_read_from(
+                &mut self,
+                address: Address,
+                bytes: &[u8],
+                buffer: &mut [u8],
+            ) -> Result<(), Error> {
+                transaction(
+                    &self.i2c,
+                    self.timeout,
+                    address,
+                    &mut [Operation::Write(bytes), Operation::Read(buffer)],
+                )
+            }
+
+            /// Starts reading `buffer.len()` bytes from the slave at `address` with the DMA
+            /// channel, see [`Transfer`]
+            ///
+            /// `buffer` must hold between 1 and 65535 bytes.
+            pub fn read_dma<CH: Channel>(
+                self,
+                address: Address,
+                mut channel: CH,
+                buffer: &'static mut [u8],
+            ) -> Transfer<Self, CH, &'static mut [u8]> {
+                assert!(!buffer.is_empty() && buffer.len() <= u16::MAX as usize);
+                let address = slave_address(address);
+
+                // a stuck START is dropped by the reset on timeout, the transfer starts anyway
+                let _ = wait_start(&self.i2c, self.timeout);
+                flush_rxdr!(self.i2c);
+
+                let rxdr = self.i2c.rxdr().as_ptr() as u32;
+                let memory = buffer.as_mut_ptr() as u32;
+                setup_channel(
+                    &mut channel,
+                    DmaMuxIndex::$RX_REQ,
+                    Direction::FromPeripheral,
+                    rxdr,
+                    memory,
+                    buffer.len(),
+                );
+                self.i2c.cr1().modify(|_, w| w.rxdmaen().set_bit());
+                let remaining = start_dma(&self.i2c, address, true, buffer.len());
+
+                Transfer {
+                    i2c: self,
+                    channel,
+                    buffer,
+                    address,
+                    remaining,
+                    read: None,
+                    done: false,
+                }
+            }
+
+            /// Starts writing `bytes` to the slave at `address` with the DMA channel, see
+            /// [`Transfer`]
+            ///
+            /// `bytes` must hold between 1 and 65535 bytes.
+            pub fn write_dma<CH: Channel>(
+                self,
+                address: Address,
+                mut channel: CH,
+                bytes: &'static [u8],
+            ) -> Transfer<Self, CH, &'static [u8]> {
+                assert!(!bytes.is_empty() && bytes.len() <= u16::MAX as usize);
+                let address = slave_address(address);
+
+                // a stuck START is dropped by the reset on timeout, the transfer starts anyway
+                let _ = wait_start(&self.i2c, self.timeout);
+                flush_txdr!(self.i2c);
+
+                let txdr = self.i2c.txdr().as_ptr() as u32;
+                let memory = bytes.as_ptr() as u32;
+                setup_channel(
+                    &mut channel,
+                    DmaMuxIndex::$TX_REQ,
+                    Direction::FromMemory,
+                    txdr,
+                    memory,
+                    bytes.len(),
+                );
+                self.i2c.cr1().modify(|_, w| w.txdmaen().set_bit());
+                let remaining = start_dma(&self.i2c, address, false, bytes.len());
+
+                Transfer {
+                    i2c: self,
+                    channel,
+                    buffer: bytes,
+                    address,
+                    remaining,
+                    read: None,
+                    done: false,
+                }
+            }
+
+            /// Starts writing `bytes` then reading into `buffer` after a repeated START with
+            /// the DMA channel, see [`Transfer`]
+            ///
+            /// Both buffers must hold between 1 and 65535 bytes.
+            pub fn write_read_dma<CH: Channel>(
+                self,
+                address: Address,
+                channel: CH,
+                bytes: &'static [u8],
+                buffer: &'static mut [u8],
+            ) -> Transfer<Self, CH, (&'static [u8], &'static mut [u8])> {
+                assert!(!buffer.is_empty() && buffer.len() <= u16::MAX as usize);
+                let read = (DmaMuxIndex::$RX_REQ, buffer.as_mut_ptr() as u32, buffer.len());
+                let Transfer { i2c, channel, buffer: bytes, address, remaining, .. } =
+                    self.write_dma(address, channel, bytes);
+
+                Transfer {
+                    i2c,
+                    channel,
+                    buffer: (bytes, buffer),
+                    address,
+                    remaining,
+                    read: Some(read),
+                    done: false,
+                }
+            }
+
             pub fn release(self) -> ($I2CX, SDA, SCL) {
                 (self.i2c, self.sda.release(), self.scl.release())
             }
         }
 
+        impl<SDA, SCL> hal_1::i2c::ErrorType for I2c<$I2CX, SDA, SCL> {
+            type Error = Error;
+        }
+
+        // Only the 7-bit flavour is implemented so integer literal addresses keep inferring,
+        // 10-bit slaves are reached through `read_from`/`write_to`/`write_read_from`
+        impl<SDA, SCL> hal_1::i2c::I2c<SevenBitAddress> for I2c<$I2CX, SDA, SCL> {
+            fn transaction(
+                &mut self,
+                address: u8,
+                operations: &mut [Operation<'_>],
+            ) -> Result<(), Self::Error> {
+                transaction(&self.i2c, self.timeout, Address::SevenBit(address), operations)
+            }
+        }
+
+
         impl<SDA, SCL> WriteRead for I2c<$I2CX, SDA, SCL> {
             type Error = Error;
 
@@ -257,7 +828,7 @@ macro_rules! i2c {
 
                 // Wait for any previous address sequence to end automatically.
                 // This could be up to 50% of a bus cycle (ie. up to 0.5/freq)
-                while self.i2c.cr2().read().start().bit_is_set() {};
+                wait_start(&self.i2c, self.timeout)?;
 
                 // flush i2c tx register
                 self.i2c.isr().write(|w| w.txe().bit(true));
@@ -286,14 +857,14 @@ macro_rules! i2c {
                 // (START has been ACKed or last byte went through)
                 // macro will return false when the tc bit is set
                 for byte in snd_buffer {
-                    busy_wait!(self.i2c, txis, bit_is_set, idx, sndlen);
+                    busy_wait!(self.i2c, txis, bit_is_set, idx, sndlen, self.timeout);
                     // Put byte on the wire
                     self.i2c.txdr().write(|w| unsafe { w.txdata().bits(*byte) });
                     idx += 1;
                 }
                 // Wait until the write finishes before beginning to read.
                 let dummy  = 0xFE;
-                busy_wait!(self.i2c, tc, bit_is_set, idx, dummy );
+                busy_wait!(self.i2c, tc, bit_is_set, idx, dummy, self.timeout);
 
                 // reSTART and prepare to receive bytes into `rcv_buffer`
                 self.i2c.cr2().write(|w| unsafe {
@@ -316,7 +887,7 @@ macro_rules! i2c {
                 idx = 0;
                 loop {
                     // Wait until we have received something. Handle all state in busy_wait macro
-                    busy_wait!(self.i2c, rxne, bit_is_set, idx, rcvlen);
+                    busy_wait!(self.i2c, rxne, bit_is_set, idx, rcvlen, self.timeout);
                     if idx < rcvlen {
                         rcv_buffer[idx] = self.i2c.rxdr().read().rxdata().bits();
                         idx +=1;
@@ -334,7 +905,7 @@ macro_rules! i2c {
 
                 // Wait for any previous address sequence to end automatically.
                 // This could be up to 50% of a bus cycle (ie. up to 0.5/freq)
-                while self.i2c.cr2().read().start().bit_is_set() {};
+                wait_start(&self.i2c, self.timeout)?;
 
                 self.i2c.cr2().modify(|_, w| unsafe {
                     w
@@ -354,7 +925,7 @@ macro_rules! i2c {
                 let mut idx = 0;
                 loop {
                     // Wait until we are allowed to send data, handle all state in busy_wait macro
-                    busy_wait!(self.i2c, txis, bit_is_set, idx, buflen);
+                    busy_wait!(self.i2c, txis, bit_is_set, idx, buflen, self.timeout);
 
                     // Put byte on the wire
                     if idx < buflen {
@@ -374,7 +945,7 @@ macro_rules! i2c {
 
                 // Wait for any previous address sequence to end automatically.
                 // This could be up to 50% of a bus cycle (ie. up to 0.5/freq)
-                while self.i2c.cr2().read().start().bit_is_set() {};
+                wait_start(&self.i2c, self.timeout)?;
                 // Flush rxdr register
                 let _ = self.i2c.rxdr().read().rxdata().bits();
 
@@ -398,7 +969,7 @@ macro_rules! i2c {
                 let mut idx = 0;
                 loop {
                     // Wait until we have received something
-                    busy_wait!(self.i2c, rxne, bit_is_set, idx, buflen);
+                    busy_wait!(self.i2c, rxne, bit_is_set, idx, buflen, self.timeout);
                     if idx < buflen {
                         bytes[idx] = self.i2c.rxdr().read().rxdata().bits();
                         idx +=1;
@@ -459,7 +1030,7 @@ macro_rules! i2c {
                 let mut idx = 0;
                 loop {
                     // wait until we are allowed to send the byte. Handle all state in macro
-                    busy_wait!(self.i2c, txis, bit_is_set, idx, buflen);
+                    busy_wait!(self.i2c, txis, bit_is_set, idx, buflen, 0);
 
                     // Put byte on the wire
                     if idx < buflen {
@@ -495,7 +1066,7 @@ macro_rules! i2c {
                 let mut idx = 0;
                 loop  {
                     // Wait until we have received something.
-                    busy_wait!(self.i2c, rxne, bit_is_set, idx, buflen);
+                    busy_wait!(self.i2c, rxne, bit_is_set, idx, buflen, 0);
 
                     // read byte from wire
                     if idx < buflen {
@@ -511,6 +1082,8 @@ macro_rules! i2c {
 i2c!(
     I2C1,
     i2c1,
+    I2C1_RX,
+    I2C1_TX,
     sda: [
         (PA10<Output<OpenDrain>>, AltFunction::AF6),
         (PB7<Output<OpenDrain>>, AltFunction::AF6),
@@ -529,6 +1102,8 @@ i2c!(
 i2c!(
     I2C2,
     i2c2,
+    I2C2_RX,
+    I2C2_TX,
     sda: [
         (PA6<Output<OpenDrain>>, AltFunction::AF6),
         (PA12<Output<OpenDrain>>, AltFunction::AF6),
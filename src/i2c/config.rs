@@ -1,5 +1,5 @@
 use crate::i2c::SlaveAddressMask;
-use crate::time::Hertz;
+use crate::time::{Hertz, MicroSecond};
 use core::cmp;
 
 pub struct Config {
@@ -11,8 +11,17 @@ pub struct Config {
     pub address_11bits: bool,
     pub slave_address_2: u8,
     pub slave_address_mask: SlaveAddressMask,
+    /// Number of status polls before a master transfer fails with `Error::Timeout`, 0 waits
+    /// forever
+    pub timeout: u32,
+    /// SCL low time after which the hardware flags a bus timeout (TIMEOUTA), also reported as
+    /// `Error::Timeout`, `None` disables it
+    pub scl_timeout: Option<MicroSecond>,
 }
 
+/// Default master transfer timeout, in status polls
+const DEFAULT_TIMEOUT: u32 = 1_000_000;
+
 impl Config {
     pub fn new(speed: Hertz) -> Self {
         Config {
@@ -24,6 +33,8 @@ impl Config {
             address_11bits: false,
             slave_address_2: 0,
             slave_address_mask: SlaveAddressMask::MaskNone,
+            timeout: DEFAULT_TIMEOUT,
+            scl_timeout: None,
         }
     }
 
@@ -37,9 +48,33 @@ impl Config {
             address_11bits: false,
             slave_address_2: 0,
             slave_address_mask: SlaveAddressMask::MaskNone,
+            timeout: DEFAULT_TIMEOUT,
+            scl_timeout: None,
         }
     }
 
+    /// Sets the master transfer timeout in status polls, 0 disables it
+    pub fn timeout(mut self, polls: u32) -> Self {
+        self.timeout = polls;
+        self
+    }
+
+    /// Enables the hardware SCL low timeout
+    ///
+    /// The timeout counts in steps of 2048 I2C kernel clock cycles, up to 4096 steps, and is
+    /// rounded down to a whole step (at least one). `timeout` must not be zero.
+    pub fn scl_timeout(mut self, timeout: MicroSecond) -> Self {
+        self.scl_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the TIMEOUTA value for the SCL low timeout at `i2c_clk`
+    pub(crate) fn timeouta_bits(&self, i2c_clk: Hertz) -> Option<u16> {
+        self.scl_timeout.map(|timeout| {
+            (crate::time::cycles(timeout, i2c_clk) / 2048).clamp(1, 0x1000) as u16 - 1
+        })
+    }
+
     pub fn disable_analog_filter(mut self) -> Self {
         self.analog_filter = false;
         self
@@ -11,6 +11,7 @@ pub mod config;
 
 use crate::rcc::*;
 pub use config::Config;
+use hal_1::i2c::{ErrorKind, NoAcknowledgeSource};
 
 #[derive(Debug, Clone, Copy)]
 pub enum SlaveAddressMask {
@@ -36,6 +37,21 @@ pub enum I2cDirection {
     MasterWriteSlaveRead = 1,
 }
 
+/// Slave address used by a master transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    /// 7-bit address, in range 0 .. 127
+    SevenBit(u8),
+    /// 10-bit address, in range 0 .. 1023
+    TenBit(u16),
+}
+
+impl From<u8> for Address {
+    fn from(addr: u8) -> Self {
+        Address::SevenBit(addr)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Event {
     AddressMatch,
@@ -51,6 +67,24 @@ pub enum Error {
     BusError,
     ArbitrationLost,
     IncorrectFrameSize(usize),
+    /// The bus did not make progress before the configured timeout, e.g. SCL held low
+    Timeout,
+    /// The DMA channel reported a transfer error
+    Dma,
+}
+
+impl hal_1::i2c::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Overrun => ErrorKind::Overrun,
+            Error::Nack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+            Error::BusError => ErrorKind::Bus,
+            Error::ArbitrationLost => ErrorKind::ArbitrationLoss,
+            Error::PECError | Error::IncorrectFrameSize(_) | Error::Timeout | Error::Dma => {
+                ErrorKind::Other
+            }
+        }
+    }
 }
 
 /// I2C SDA pin
@@ -63,6 +97,10 @@ pub trait SDAPin<I2C> {
 pub trait SCLPin<I2C> {
     fn setup(&self);
     fn release(self) -> Self;
+
+    /// Drives SCL as an open-drain GPIO for `pulses` clock cycles, then hands it back to the
+    /// I2C peripheral
+    fn clock_out(&mut self, pulses: u8, delay: &mut impl hal_1::delay::DelayNs);
 }
 
 pub trait I2cExt<I2C> {
@@ -84,6 +122,7 @@ pub struct I2c<I2C, SDA, SCL> {
     i2c: I2C,
     sda: SDA,
     scl: SCL,
+    timeout: u32,
 }
 
 #[cfg(feature = "i2c-nonblocking")]
@@ -128,6 +128,17 @@ macro_rules! i2c {
                 fn release(self) -> Self {
                     self.into_open_drain_output()
                 }
+
+                fn clock_out(&mut self, pulses: u8, delay: &mut impl hal_1::delay::DelayNs) {
+                    self.internal_set_mode(Dynamic::OutputOpenDrain);
+                    for _ in 0..pulses {
+                        hal::digital::v2::OutputPin::set_low(self).ok();
+                        delay.delay_us(5);
+                        hal::digital::v2::OutputPin::set_high(self).ok();
+                        delay.delay_us(5);
+                    }
+                    SCLPin::<$I2CX>::setup(self);
+                }
             }
         )+
 
@@ -164,7 +175,7 @@ macro_rules! i2c {
                 i2c.cr1.modify(|_, w| w.pe().clear_bit());
 
                 // Setup protocol timings
-                let timing_bits = config.timing_bits(rcc.clocks.apb_clk);
+                let timing_bits = config.timing_bits(rcc.clocks.apb_clk());
                 i2c.timingr.write(|w| unsafe { w.bits(timing_bits) });
 
                 // Enable the I2C processing
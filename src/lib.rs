@@ -9,6 +9,9 @@ extern crate void;
 
 pub extern crate cortex_m;
 pub extern crate embedded_hal as hal;
+pub extern crate embedded_hal_1 as hal_1;
+#[cfg(feature = "async")]
+pub extern crate embedded_hal_async as hal_async;
 pub extern crate nb;
 pub extern crate stm32c0;
 
@@ -31,7 +34,9 @@ pub use crate::stm32::interrupt;
 
 pub mod analog;
 pub mod crc;
+pub mod dma;
 pub mod exti;
+pub mod flash;
 pub mod gpio;
 pub mod i2c;
 pub mod power;
@@ -39,6 +44,7 @@ pub mod prelude;
 pub mod rcc;
 pub mod rtc;
 pub mod serial;
+pub mod signature;
 pub mod spi;
 pub mod time;
 pub mod timer;
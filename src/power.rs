@@ -1,16 +1,38 @@
 //! Power control
 
+use cortex_m::peripheral::SCB;
+
 use crate::{
     gpio::*,
-    rcc::{Enable, Rcc},
+    rcc::{self, Enable, Rcc},
     stm32::PWR,
 };
 
+/// Deep sleep mode selected by the LPMS field of PWR_CR1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LowPowerMode {
-    StopMode1 = 0b000,
-    StopMode2 = 0b001,
-    Standby = 0b011,
-    Shutdown = 0b111,
+    /// Stop mode, the SRAM and the registers are retained and the execution resumes on wakeup
+    Stop,
+    /// Standby mode, the wakeup goes through a reset
+    Standby,
+    /// Shutdown mode, the wakeup goes through a reset and the LSI and BOR are off
+    Shutdown,
+    #[deprecated(note = "the C0 has a single Stop mode, use `LowPowerMode::Stop`")]
+    StopMode1,
+    #[deprecated(note = "the C0 has a single Stop mode, use `LowPowerMode::Stop`")]
+    StopMode2,
+}
+
+impl LowPowerMode {
+    /// Returns the LPMS encoding of the mode
+    #[allow(deprecated)]
+    pub fn bits(self) -> u8 {
+        match self {
+            LowPowerMode::Stop | LowPowerMode::StopMode1 | LowPowerMode::StopMode2 => 0b000,
+            LowPowerMode::Standby => 0b011,
+            LowPowerMode::Shutdown => 0b100,
+        }
+    }
 }
 
 pub enum PowerMode {
@@ -108,6 +130,81 @@ impl Power {
         };
     }
 
+    /// Enters Stop mode on WFI and waits for an interrupt
+    ///
+    /// The system restarts on HSISYS when leaving Stop, the system clock selected before is
+    /// switched back on so the `Clocks` in `rcc` stay valid. The peripheral clock enables are
+    /// retained, but peripherals clocked from a source that stops in Stop mode (HSE) may need
+    /// to be reconfigured. SLEEPDEEP is cleared again on the way out for the following WFI.
+    ///
+    /// Fails if the HSE doesn't restart, the system then keeps running on HSISYS.
+    pub fn enter_stop(&mut self, scb: &mut SCB, rcc: &mut Rcc) -> Result<(), rcc::Error> {
+        let sw = rcc.cfgr().read().sw().bits();
+        let hse_bypass = rcc.cr().read().hsebyp().bit_is_set();
+
+        self.set_low_power_mode(LowPowerMode::Stop);
+        scb.set_sleepdeep();
+        cortex_m::asm::dsb();
+        cortex_m::asm::wfi();
+        scb.clear_sleepdeep();
+
+        if rcc.cfgr().read().sws().bits() != sw {
+            if sw == 0b001 {
+                rcc.start_hse(hse_bypass)?;
+            }
+            rcc.cfgr().modify(|_, w| unsafe { w.sw().bits(sw) });
+            while rcc.cfgr().read().sws().bits() != sw {}
+        }
+        Ok(())
+    }
+
+    /// Enters Standby mode, the SRAM content is lost and the wakeup goes through a reset
+    ///
+    /// Check [`Self::get_standby_flag()`] after the reset to tell a Standby wakeup apart.
+    pub fn enter_standby(&mut self, scb: &mut SCB) -> ! {
+        self.enter_reset_mode(LowPowerMode::Standby, scb)
+    }
+
+    /// Enters Shutdown mode, the lowest power mode, the wakeup goes through a reset
+    pub fn enter_shutdown(&mut self, scb: &mut SCB) -> ! {
+        self.enter_reset_mode(LowPowerMode::Shutdown, scb)
+    }
+
+    fn enter_reset_mode(&mut self, mode: LowPowerMode, scb: &mut SCB) -> ! {
+        self.set_low_power_mode(mode);
+        scb.set_sleepdeep();
+        cortex_m::asm::dsb();
+        loop {
+            // a pending interrupt returns from WFI without entering the mode
+            cortex_m::asm::wfi();
+        }
+    }
+
+    fn set_low_power_mode(&mut self, mode: LowPowerMode) {
+        self.rb
+            .cr1()
+            .modify(|_, w| unsafe { w.lpms().bits(mode.bits()) });
+    }
+
+    /// Enters Sleep mode until an interrupt, the CPU clock stops and the peripherals keep
+    /// running
+    ///
+    /// The C0 has no low-power run mode (no LPR bit), lowering the HSISYS divider is the way to
+    /// save power while running.
+    pub fn enter_sleep(&mut self, scb: &mut SCB) {
+        scb.clear_sleepdeep();
+        cortex_m::asm::dsb();
+        cortex_m::asm::wfi();
+    }
+
+    /// Enters Sleep mode until an event, set `SEVONPEND` in SCB to also wake up on pending
+    /// interrupts that are disabled in the NVIC
+    pub fn wait_for_event(&mut self, scb: &mut SCB) {
+        scb.clear_sleepdeep();
+        cortex_m::asm::dsb();
+        cortex_m::asm::wfe();
+    }
+
     pub fn set_mode(&mut self, _mode: PowerMode) {
         todo!();
         // match mode {
@@ -1,6 +1,8 @@
 pub use crate::analog::adc::AdcExt as _;
 pub use crate::crc::CrcExt as _;
+pub use crate::dma::DmaExt as _;
 pub use crate::exti::ExtiExt as _;
+pub use crate::flash::FlashExt as _;
 pub use crate::gpio::GpioExt as _;
 pub use crate::i2c::I2cExt as _;
 pub use crate::power::PowerExt as _;
@@ -11,6 +13,7 @@ pub use crate::rtc::RtcExt as _;
 pub use crate::serial::SerialExt as _;
 pub use crate::spi::SpiExt as _;
 pub use crate::time::U32Ext as _;
+pub use crate::timer::capture::InputCaptureExt as _;
 pub use crate::timer::delay::DelayExt as _;
 pub use crate::timer::opm::OpmExt as _;
 pub use crate::timer::pwm::PwmExt as _;
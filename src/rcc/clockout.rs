@@ -76,6 +76,20 @@ pub trait MCOExt<PIN> {
     fn release(self) -> PIN;
 }
 
+impl Rcc {
+    /// Routes `src` divided by `psc` to the MCO `pin` and enables the output
+    ///
+    /// Prescalers above `Div128` are clamped to `Div128`.
+    pub fn configure_mco<PIN>(&mut self, src: MCOSrc, psc: Prescaler, pin: PIN) -> Mco<PIN>
+    where
+        PIN: MCOExt<PIN>,
+    {
+        let mut mco = pin.mco(src, psc, self);
+        mco.enable();
+        mco
+    }
+}
+
 macro_rules! mco {
     ($($PIN:ty),+) => {
         $(
@@ -103,7 +117,7 @@ macro_rules! mco {
                             0b011
                         },
                         MCOSrc::HSE => {
-                            rcc.enable_hse(false);
+                            rcc.start_hse(false).expect("HSE not ready");
                             0b100
                         },
                         MCOSrc::LSI => {
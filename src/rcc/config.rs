@@ -16,8 +16,12 @@ pub enum Prescaler {
 }
 
 /// System clock mux source
+///
+/// The C0 family has no PLL, the highest system clock is the 48 MHz HSI selected with
+/// `HSI(Prescaler::NotDivided)`.
 pub enum SysClockSrc {
     LSI,
+    /// HSI48 divided by HSIDIV (HSISYS)
     HSI(Prescaler),
     HSE(Hertz),
     HSE_BYPASS(Hertz),
@@ -29,6 +33,7 @@ pub enum SysClockSrc {
 pub enum MCOSrc {
     LSI,
     SysClk,
+    /// HSI48, before the HSIDIV prescaler
     HSI,
     HSE,
     LSE,
@@ -91,12 +96,33 @@ impl Config {
         self
     }
 
+    /// Sets the HPRE prescaler between SYSCLK and HCLK
+    ///
+    /// Panics on `Div32`, which HPRE does not provide.
     pub fn ahb_psc(mut self, psc: Prescaler) -> Self {
+        assert!(
+            !matches!(psc, Prescaler::Div32),
+            "AHB prescaler can't divide by 32"
+        );
         self.ahb_psc = psc;
         self
     }
 
+    /// Sets the PPRE prescaler between HCLK and PCLK
+    ///
+    /// Panics on prescalers above `Div16`. Timers on APB run at twice PCLK when it is divided.
     pub fn apb_psc(mut self, psc: Prescaler) -> Self {
+        assert!(
+            matches!(
+                psc,
+                Prescaler::NotDivided
+                    | Prescaler::Div2
+                    | Prescaler::Div4
+                    | Prescaler::Div8
+                    | Prescaler::Div16
+            ),
+            "APB prescaler can't divide by more than 16"
+        );
         self.apb_psc = psc;
         self
     }
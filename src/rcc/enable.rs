@@ -141,4 +141,5 @@ bus! {
 #[cfg(feature = "stm32c071")]
 bus! {
     I2C2 => (APB1, i2c2en, i2c2smen, i2c2rst), // 21
+    SPI2 => (APB1, spi2en, spi2smen, spi2rst), // 14
 }
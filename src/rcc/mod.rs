@@ -1,4 +1,4 @@
-use crate::stm32::{rcc, RCC};
+use crate::stm32::{rcc, FLASH, RCC};
 use crate::time::Hertz;
 use fugit::RateExtU32;
 
@@ -12,6 +12,22 @@ pub use config::*;
 /// HSI frequency
 pub const HSI_FREQ: u32 = 48_000_000;
 
+/// LSI nominal frequency
+pub const LSI_FREQ: u32 = 32_000;
+
+/// LSE crystal frequency
+pub const LSE_FREQ: u32 = 32_768;
+
+/// Number of HSERDY polls before giving up on the external oscillator
+const HSE_STARTUP_TIMEOUT: u32 = 0x0010_0000;
+
+/// RCC error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// HSERDY was not set before the startup timeout expired
+    HseTimeout,
+}
+
 /// Clock frequencies
 #[derive(Clone, Copy)]
 pub struct Clocks {
@@ -27,6 +43,38 @@ pub struct Clocks {
     pub apb_tim_clk: Hertz,
 }
 
+impl Clocks {
+    /// System clock frequency
+    pub fn sys_clk(&self) -> Hertz {
+        self.sys_clk
+    }
+
+    /// SysTick external clock frequency (HCLK / 8)
+    pub fn core_clk(&self) -> Hertz {
+        self.core_clk
+    }
+
+    /// AHB (HCLK) frequency
+    pub fn ahb_clk(&self) -> Hertz {
+        self.ahb_clk
+    }
+
+    /// APB (PCLK) frequency
+    pub fn apb_clk(&self) -> Hertz {
+        self.apb_clk
+    }
+
+    /// APB timers frequency
+    pub fn apb_tim_clk(&self) -> Hertz {
+        self.apb_tim_clk
+    }
+
+    /// HSI48 oscillator frequency, before the HSIDIV prescaler
+    pub fn hsi48_clk(&self) -> Hertz {
+        HSI_FREQ.Hz()
+    }
+}
+
 impl Default for Clocks {
     fn default() -> Clocks {
         Clocks {
@@ -39,6 +87,25 @@ impl Default for Clocks {
     }
 }
 
+/// Cause of the last reset, as latched in RCC_CSR2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetReason {
+    /// Illegal Stop or Standby mode entry
+    LowPower,
+    WindowWatchdog,
+    IndependentWatchdog,
+    /// `SYSRESETREQ` from the core
+    Software,
+    /// Power-on or brown-out reset
+    PowerOn,
+    /// Option byte loading
+    OptionByteLoad,
+    /// External NRST pin
+    Pin,
+    /// No reset flag set, e.g. after [`Rcc::clear_reset_flags`]
+    Unknown,
+}
+
 /// Constrained RCC peripheral
 pub struct Rcc {
     /// Clock configuration
@@ -57,14 +124,17 @@ impl core::ops::Deref for Rcc {
 
 impl Rcc {
     /// Apply clock configuration
+    ///
+    /// Panics if an HSE system clock is requested and the oscillator does not start, use
+    /// [`Rcc::enable_hse`] to handle that case.
     pub fn freeze(self, cfg: Config) -> Self {
         let (sys_clk, sw_bits) = match cfg.sys_mux {
             SysClockSrc::HSE(freq) => {
-                self.enable_hse(false);
+                self.start_hse(false).expect("HSE not ready");
                 (freq, 0b001)
             }
             SysClockSrc::HSE_BYPASS(freq) => {
-                self.enable_hse(true);
+                self.start_hse(true).expect("HSE not ready");
                 (freq, 0b001)
             }
             SysClockSrc::LSE(freq) => {
@@ -75,10 +145,7 @@ impl Rcc {
                 self.enable_lse(true);
                 (freq, 0b100)
             }
-            SysClockSrc::LSI => {
-                self.enable_lsi();
-                (32_768.Hz(), 0b011)
-            }
+            SysClockSrc::LSI => (self.enable_lsi(), 0b011),
             SysClockSrc::HSI(prs) => {
                 self.enable_hsi();
                 let (freq, div_bits) = match prs {
@@ -91,6 +158,10 @@ impl Rcc {
                     Prescaler::Div128 => (HSI_FREQ / 128, 0b111),
                     _ => (HSI_FREQ, 0b000),
                 };
+                // HSIDIV takes effect at once if HSISYS already drives SYSCLK
+                if Self::required_flash_latency(freq.Hz()) > self.flash_latency() {
+                    self.set_flash_latency(freq.Hz());
+                }
                 self.cr().write(|w| unsafe { w.hsidiv().bits(div_bits) });
                 (freq.Hz(), 0b000)
             }
@@ -116,6 +187,10 @@ impl Rcc {
             _ => (ahb_freq, ahb_freq, 0b000),
         };
 
+        if Self::required_flash_latency(sys_clk) > self.flash_latency() {
+            self.set_flash_latency(sys_clk);
+        }
+
         self.cfgr().modify(|_, w| unsafe {
             w.hpre()
                 .bits(ahb_psc_bits)
@@ -127,6 +202,8 @@ impl Rcc {
 
         while self.cfgr().read().sws().bits() != sw_bits {}
 
+        self.set_flash_latency(sys_clk);
+
         Rcc {
             rb: self.rb,
             clocks: Clocks {
@@ -144,21 +221,141 @@ impl Rcc {
         while self.cr().read().hsirdy().bit_is_clear() {}
     }
 
-    pub(crate) fn enable_hse(&self, bypass: bool) {
+    /// Enables the HSE oscillator running at `freq` and switches the system clock to it
+    ///
+    /// The AHB and APB prescalers are kept, the clock frequencies are updated accordingly. If
+    /// HSERDY does not assert in time the oscillator is turned off again, the system clock is
+    /// left untouched and [`Error::HseTimeout`] is returned.
+    pub fn enable_hse(&mut self, freq: Hertz, bypass: bool) -> Result<(), Error> {
+        self.start_hse(bypass)?;
+
+        if Self::required_flash_latency(freq) > self.flash_latency() {
+            self.set_flash_latency(freq);
+        }
+
+        self.cfgr().modify(|_, w| unsafe { w.sw().bits(0b001) });
+        while self.cfgr().read().sws().bits() != 0b001 {}
+
+        self.set_flash_latency(freq);
+
+        let cfgr = self.cfgr().read();
+        let ahb_freq = match cfgr.hpre().bits() {
+            bits @ 0b1000..=0b1011 => freq.raw() >> (bits - 0b0111),
+            bits @ 0b1100..=0b1111 => freq.raw() >> (bits - 0b0110),
+            _ => freq.raw(),
+        };
+        let (apb_freq, apb_tim_freq) = match cfgr.ppre().bits() {
+            bits @ 0b100..=0b111 => {
+                let apb_freq = ahb_freq >> (bits - 0b011);
+                (apb_freq, apb_freq * 2)
+            }
+            _ => (ahb_freq, ahb_freq),
+        };
+
+        self.clocks = Clocks {
+            sys_clk: freq,
+            ahb_clk: ahb_freq.Hz(),
+            apb_clk: apb_freq.Hz(),
+            apb_tim_clk: apb_tim_freq.Hz(),
+            core_clk: (ahb_freq / 8).Hz(),
+        };
+        Ok(())
+    }
+
+    /// Flash wait states needed to run from a `sys_clk` system clock
+    ///
+    /// Zero wait states are allowed up to 24 MHz, one above.
+    pub fn required_flash_latency(sys_clk: Hertz) -> u8 {
+        if sys_clk.raw() <= 24_000_000 {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Flash wait states currently programmed in FLASH_ACR
+    pub fn flash_latency(&self) -> u8 {
+        // NOTE(unsafe) atomic read with no side effects
+        let flash = unsafe { &*FLASH::ptr() };
+        flash.acr().read().latency().bits()
+    }
+
+    fn set_flash_latency(&self, sys_clk: Hertz) {
+        let latency = Self::required_flash_latency(sys_clk);
+        // NOTE(unsafe) FLASH_ACR is only written while the clocks are reconfigured through Rcc
+        let flash = unsafe { &*FLASH::ptr() };
+        flash
+            .acr()
+            .modify(|_, w| unsafe { w.latency().bits(latency).prften().set_bit() });
+        while flash.acr().read().latency().bits() != latency {}
+    }
+
+    pub(crate) fn start_hse(&self, bypass: bool) -> Result<(), Error> {
         self.cr()
             .modify(|_, w| w.hseon().set_bit().hsebyp().bit(bypass));
-        while self.cr().read().hserdy().bit_is_clear() {}
+        for _ in 0..HSE_STARTUP_TIMEOUT {
+            if self.cr().read().hserdy().bit_is_set() {
+                return Ok(());
+            }
+        }
+        self.cr().modify(|_, w| w.hseon().clear_bit());
+        Err(Error::HseTimeout)
     }
 
-    pub(crate) fn enable_lsi(&self) {
+    /// Enables the LSI oscillator, waits until it is ready and returns its nominal frequency
+    pub fn enable_lsi(&self) -> Hertz {
         self.csr2().modify(|_, w| w.lsion().set_bit());
         while self.csr2().read().lsirdy().bit_is_clear() {}
+        LSI_FREQ.Hz()
     }
 
-    pub(crate) fn enable_lse(&self, bypass: bool) {
+    /// Enables the LSE oscillator, or accepts an external clock on OSC32_IN with `bypass`
+    ///
+    /// Waits until LSERDY is set and returns the 32.768 kHz crystal frequency. The RTC domain
+    /// of the C0 has no write protection, so no backup domain unlocking is required.
+    pub fn enable_lse(&self, bypass: bool) -> Hertz {
         self.csr1()
             .modify(|_, w| w.lseon().set_bit().lsebyp().bit(bypass));
         while self.csr1().read().lserdy().bit_is_clear() {}
+        LSE_FREQ.Hz()
+    }
+
+    /// Returns the cause of the last reset
+    ///
+    /// Internal resets also drive NRST, so the pin flag is only reported when no other flag is
+    /// set. The flags are sticky until [`Rcc::clear_reset_flags`] is called.
+    pub fn reset_reason(&self) -> ResetReason {
+        let csr = self.csr2().read();
+        if csr.lpwrrstf().bit_is_set() {
+            ResetReason::LowPower
+        } else if csr.wwdgrstf().bit_is_set() {
+            ResetReason::WindowWatchdog
+        } else if csr.iwdgrstf().bit_is_set() {
+            ResetReason::IndependentWatchdog
+        } else if csr.sftrstf().bit_is_set() {
+            ResetReason::Software
+        } else if csr.pwrrstf().bit_is_set() {
+            ResetReason::PowerOn
+        } else if csr.oblrstf().bit_is_set() {
+            ResetReason::OptionByteLoad
+        } else if csr.pinrstf().bit_is_set() {
+            ResetReason::Pin
+        } else {
+            ResetReason::Unknown
+        }
+    }
+
+    /// Clears all the reset flags
+    pub fn clear_reset_flags(&mut self) {
+        self.csr2().modify(|_, w| w.rmvf().set_bit());
+    }
+
+    /// Requests a system reset through the SCB, the reset is reported as
+    /// [`ResetReason::Software`]
+    ///
+    /// Outstanding memory accesses complete before the reset is requested.
+    pub fn reset_system(&mut self) -> ! {
+        cortex_m::peripheral::SCB::sys_reset()
     }
 
     pub(crate) fn enable_pwr_clock(&self) {
@@ -170,28 +367,39 @@ impl Rcc {
         self.apbenr1()
             .modify(|_, w| w.rtcapben().set_bit().pwren().set_bit());
         self.apbsmenr1().modify(|_, w| w.rtcapbsmen().set_bit());
-        self.csr1().modify(|_, w| w.rtcrst().set_bit());
         let rtc_sel = match src {
             RTCSrc::LSE | RTCSrc::LSE_BYPASS => 0b01,
             RTCSrc::LSI => 0b10,
             RTCSrc::HSE | RTCSrc::HSE_BYPASS => 0b11,
         };
 
-        self.csr1().modify(|_, w| unsafe {
-            w.rtcsel()
-                .bits(rtc_sel)
-                .rtcen()
-                .set_bit()
-                .rtcrst()
-                .clear_bit()
-        });
+        // RTCSEL can only be changed through an RTC domain reset, which also clears the
+        // calendar, so a running RTC on the same source is kept as is
+        let csr1 = self.csr1().read();
+        if csr1.rtcen().bit_is_clear() || csr1.rtcsel().bits() != rtc_sel {
+            self.csr1().modify(|_, w| w.rtcrst().set_bit());
+            self.csr1().modify(|_, w| unsafe {
+                w.rtcsel()
+                    .bits(rtc_sel)
+                    .rtcen()
+                    .set_bit()
+                    .rtcrst()
+                    .clear_bit()
+            });
+        }
 
         match src {
-            RTCSrc::LSE => self.enable_lse(false),
-            RTCSrc::LSE_BYPASS => self.enable_lse(true),
-            RTCSrc::LSI => self.enable_lsi(),
-            RTCSrc::HSE => self.enable_hse(false),
-            RTCSrc::HSE_BYPASS => self.enable_hse(true),
+            RTCSrc::LSE => {
+                self.enable_lse(false);
+            }
+            RTCSrc::LSE_BYPASS => {
+                self.enable_lse(true);
+            }
+            RTCSrc::LSI => {
+                self.enable_lsi();
+            }
+            RTCSrc::HSE => self.start_hse(false).expect("HSE not ready"),
+            RTCSrc::HSE_BYPASS => self.start_hse(true).expect("HSE not ready"),
         };
     }
 }
@@ -228,7 +436,7 @@ pub trait Enable: RccBus {
     /// Enables peripheral
     fn enable(rcc: &mut Rcc);
 
-    /// Disables peripheral
+    /// Disables peripheral by gating its clock off in the bus enable register
     fn disable(rcc: &mut Rcc);
 
     /// Check if peripheral enabled
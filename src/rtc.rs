@@ -1,9 +1,17 @@
 //! Real Time Clock
+//!
+//! The RTC registers sit in the always-on domain: the calendar keeps running through Stop and
+//! Standby and across system resets. The C0 has no backup domain write protection, so there
+//! is no PWR DBP bit to unlock, only the RTC write protection key sequence.
 use crate::gpio::*;
 use crate::rcc::{RTCSrc, Rcc};
 use crate::stm32::RTC;
 use crate::time::*;
 
+/// First year of the calendar, the hardware treats every stored year divisible by 4 as a leap
+/// year so the base has to be a multiple of 4
+pub const YEAR_BASE: u32 = 2000;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum RtcHourFormat {
     H24,
@@ -16,10 +24,9 @@ pub enum RtcCalibrationFrequency {
     F512Hz,
 }
 
+/// RTC interrupt events, the C0 RTC only has alarm A and the timestamp
 pub enum Event {
-    WakeupTimer,
     AlarmA,
-    AlarmB,
     Timestamp,
 }
 
@@ -107,9 +114,65 @@ pub struct Rtc {
 }
 
 impl Rtc {
+    /// Clocks the RTC from `src`
+    ///
+    /// A calendar already running from the same source is left untouched. Otherwise the
+    /// prescalers are set for a 1 Hz calendar from the LSE or the LSI, an HSE source needs
+    /// [`Self::set_prescalers()`].
     pub fn new(rtc: RTC, src: RTCSrc, rcc: &mut Rcc) -> Self {
         rcc.enable_rtc(src);
-        Rtc { rb: rtc }
+        let mut rtc = Rtc { rb: rtc };
+        if rtc.rb.icsr().read().inits().bit_is_clear() {
+            match src {
+                RTCSrc::LSE | RTCSrc::LSE_BYPASS => rtc.set_prescalers(127, 255),
+                RTCSrc::LSI => rtc.set_prescalers(127, 249),
+                RTCSrc::HSE | RTCSrc::HSE_BYPASS => {}
+            }
+        }
+        rtc
+    }
+
+    /// Sets the asynchronous and synchronous prescalers, the calendar is clocked at
+    /// RTCCLK / ((`prediv_a` + 1) * (`prediv_s` + 1))
+    pub fn set_prescalers(&mut self, prediv_a: u8, prediv_s: u16) {
+        self.modify(|rb| {
+            rb.prer()
+                .write(|w| w.prediv_a().set(prediv_a).prediv_s().set(prediv_s));
+        });
+    }
+
+    /// Sets the calendar date and time in one go
+    pub fn set_datetime(&mut self, date: &Date, time: &Time) {
+        self.set_date(date);
+        self.set_time(time);
+    }
+
+    /// Reads the calendar date and time
+    ///
+    /// TR is read before DR, reading TR freezes the DR shadow register until DR is read so
+    /// both values belong to the same second.
+    pub fn datetime(&self) -> (Date, Time) {
+        self.wait_shadow_sync();
+        let timer = self.rb.tr().read();
+        let date = self.rb.dr().read();
+        (
+            Date::new(
+                (bcd2_decode(date.yt().bits(), date.yu().bits()) + YEAR_BASE).year(),
+                bcd2_decode(date.mt().bit() as u8, date.mu().bits()).month(),
+                bcd2_decode(date.dt().bits(), date.du().bits()).day(),
+            ),
+            Time::new(
+                bcd2_decode(timer.ht().bits(), timer.hu().bits()).hours(),
+                bcd2_decode(timer.mnt().bits(), timer.mnu().bits()).minutes(),
+                bcd2_decode(timer.st().bits(), timer.su().bits()).secs(),
+                self.rb.cr().read().bkp().bit(),
+            ),
+        )
+    }
+
+    /// Waits for the calendar shadow registers to be refreshed after an init or a wakeup
+    fn wait_shadow_sync(&self) {
+        while self.rb.icsr().read().rsf().bit_is_clear() {}
     }
 
     pub fn set_hour_format(&mut self, fmt: RtcHourFormat) {
@@ -119,8 +182,17 @@ impl Rtc {
         });
     }
 
+    /// Sets the calendar date
+    ///
+    /// # Panics
+    ///
+    /// The year must be in [`YEAR_BASE`] to `YEAR_BASE + 99`.
     pub fn set_date(&mut self, date: &Date) {
-        let (yt, yu) = bcd2_encode(date.year - 1970);
+        assert!(
+            (YEAR_BASE..YEAR_BASE + 100).contains(&date.year),
+            "year out of the RTC range"
+        );
+        let (yt, yu) = bcd2_encode(date.year - YEAR_BASE);
         let (mt, mu) = bcd2_encode(date.month);
         let (dt, du) = bcd2_encode(date.day);
 
@@ -139,7 +211,7 @@ impl Rtc {
                     .yu()
                     .bits(yu)
                     .wdu()
-                    .bits(date.day as u8)
+                    .bits(week_day(date))
             });
         });
     }
@@ -165,33 +237,33 @@ impl Rtc {
                     .pm()
                     .clear_bit()
             });
-            rb.cr().modify(|_, w| w.fmt().bit(time.daylight_savings));
+            rb.cr().modify(|_, w| w.bkp().bit(time.daylight_savings));
         });
     }
 
     pub fn get_time(&self) -> Time {
-        let timer = self.rb.tr().read();
-        Time::new(
-            bcd2_decode(timer.ht().bits(), timer.hu().bits()).hours(),
-            bcd2_decode(timer.mnt().bits(), timer.mnu().bits()).minutes(),
-            bcd2_decode(timer.st().bits(), timer.su().bits()).secs(),
-            self.rb.cr().read().fmt().bit(),
-        )
+        self.datetime().1
     }
 
     pub fn get_date(&self) -> Date {
-        let date = self.rb.dr().read();
-        Date::new(
-            (bcd2_decode(date.yt().bits(), date.yu().bits()) + 1970).year(),
-            bcd2_decode(date.mt().bit() as u8, date.mu().bits()).month(),
-            bcd2_decode(date.dt().bits(), date.du().bits()).day(),
-        )
+        self.datetime().0
     }
 
     pub fn get_week_day(&self) -> u8 {
         self.rb.dr().read().wdu().bits()
     }
 
+    /// Programs alarm A, enables it and its interrupt
+    ///
+    /// The RTC interrupts reach the EXTI on the direct line 19, enable it with
+    /// `exti.wakeup(exti::Event::RTC)` to wake up from Stop. A direct line has no EXTI pending
+    /// bit, the alarm flag is cleared with `unpend(Event::AlarmA)`.
+    pub fn set_alarm(&mut self, alarm: impl Into<Alarm>) {
+        self.set_alarm_a(alarm);
+        self.listen(Event::AlarmA);
+    }
+
+    /// Programs and enables alarm A, the calendar keeps running meanwhile
     pub fn set_alarm_a(&mut self, alarm: impl Into<Alarm>) {
         let alarm = alarm.into();
         let (dt, du) = bcd2_encode(alarm.day.unwrap_or_default());
@@ -199,7 +271,11 @@ impl Rtc {
         let (mt, mu) = bcd2_encode(alarm.minutes.unwrap_or_default());
         let (st, su) = bcd2_encode(alarm.seconds.unwrap_or_default());
 
-        self.modify(|rb| {
+        self.unlocked(|rb| {
+            // ALRMAR can only be written while the alarm is off
+            rb.cr().modify(|_, w| w.alrae().clear_bit());
+            while rb.icsr().read().alrawf().bit_is_clear() {}
+
             rb.alrmassr().write(|w| unsafe {
                 w.maskss()
                     .bits(alarm.subseconds_mask_bits)
@@ -222,82 +298,46 @@ impl Rtc {
                 w.su().bits(su)
             });
 
+            rb.scr().write(|w| w.calraf().set_bit());
             rb.cr().modify(|_, w| w.alrae().set_bit());
         });
     }
 
-    pub fn set_alarm_b(&mut self, _alarm: Alarm) {
-        // let (dt, du) = bcd2_encode(alarm.day.unwrap_or_default() as u32);
-        // let (ht, hu) = bcd2_encode(alarm.hours.unwrap_or_default() as u32);
-        // let (mt, mu) = bcd2_encode(alarm.minutes.unwrap_or_default() as u32);
-        // let (st, su) = bcd2_encode(alarm.seconds.unwrap_or_default() as u32);
-
-        // self.modify(|rb| {
-        //     rb.alrmbssr.write(|w| unsafe {
-        //         w.maskss()
-        //             .bits(alarm.subseconds_mask_bits)
-        //             .ss()
-        //             .bits(alarm.subseconds)
-        //     });
-        //     rb.alrmbr.write(|w| unsafe {
-        //         w.wdsel().bit(alarm.use_weekday);
-        //         w.msk1().bit(alarm.seconds.is_none());
-        //         w.msk2().bit(alarm.minutes.is_none());
-        //         w.msk3().bit(alarm.hours.is_none());
-        //         w.msk4().bit(alarm.day.is_none());
-        //         w.dt().bits(dt);
-        //         w.du().bits(du);
-        //         w.ht().bits(ht);
-        //         w.hu().bits(hu);
-        //         w.mnt().bits(mt);
-        //         w.mnu().bits(mu);
-        //         w.st().bits(st);
-        //         w.su().bits(su)
-        //     });
-
-        //     rb.cr.modify(|_, w| w.alrbe().set_bit());
-        // });
-        todo!();
+    /// Disables alarm A and its interrupt
+    pub fn disable_alarm(&mut self) {
+        self.unlocked(|rb| {
+            rb.cr()
+                .modify(|_, w| w.alrae().clear_bit().alraie().clear_bit());
+            rb.scr().write(|w| w.calraf().set_bit());
+        });
     }
 
-    pub fn listen(&mut self, _ev: Event) {
-        // self.modify(|rb| match ev {
-        //     Event::WakeupTimer => rb.cr.modify(|_, w| w.wutie().set_bit()),
-        //     Event::AlarmA => rb.cr.modify(|_, w| w.alraie().set_bit()),
-        //     Event::AlarmB => rb.cr.modify(|_, w| w.alrbie().set_bit()),
-        //     Event::Timestamp => rb.cr.modify(|_, w| w.tsie().set_bit()),
-        // })
-        todo!();
+    pub fn listen(&mut self, ev: Event) {
+        self.unlocked(|rb| match ev {
+            Event::AlarmA => _ = rb.cr().modify(|_, w| w.alraie().set_bit()),
+            Event::Timestamp => _ = rb.cr().modify(|_, w| w.tsie().set_bit()),
+        })
     }
 
-    pub fn unlisten(&mut self, _ev: Event) {
-        // self.modify(|rb| match ev {
-        //     Event::WakeupTimer => rb.cr.modify(|_, w| w.wutie().clear_bit()),
-        //     Event::AlarmA => rb.cr.modify(|_, w| w.alraie().clear_bit()),
-        //     Event::AlarmB => rb.cr.modify(|_, w| w.alrbie().clear_bit()),
-        //     Event::Timestamp => rb.cr.modify(|_, w| w.tsie().clear_bit()),
-        // })
-        todo!();
+    pub fn unlisten(&mut self, ev: Event) {
+        self.unlocked(|rb| match ev {
+            Event::AlarmA => _ = rb.cr().modify(|_, w| w.alraie().clear_bit()),
+            Event::Timestamp => _ = rb.cr().modify(|_, w| w.tsie().clear_bit()),
+        })
     }
 
-    pub fn is_pending(&self, _ev: Event) -> bool {
-        // match ev {
-        //     Event::WakeupTimer => self.rb.sr.read().wutf().bit_is_set(),
-        //     Event::AlarmA => self.rb.sr.read().alraf().bit_is_set(),
-        //     Event::AlarmB => self.rb.sr.read().alrbf().bit_is_set(),
-        //     Event::Timestamp => self.rb.sr.read().tsf().bit_is_set(),
-        // }
-        todo!();
+    pub fn is_pending(&self, ev: Event) -> bool {
+        match ev {
+            Event::AlarmA => self.rb.sr().read().alraf().bit_is_set(),
+            Event::Timestamp => self.rb.sr().read().tsf().bit_is_set(),
+        }
     }
 
-    pub fn unpend(&mut self, _ev: Event) {
-        // self.modify(|rb| match ev {
-        //     Event::WakeupTimer => rb.scr.modify(|_, w| w.cwutf().set_bit()),
-        //     Event::AlarmA => rb.scr.modify(|_, w| w.calraf().set_bit()),
-        //     Event::AlarmB => rb.scr.modify(|_, w| w.calrbf().set_bit()),
-        //     Event::Timestamp => rb.scr.modify(|_, w| w.ctsf().set_bit()),
-        // });
-        todo!();
+    pub fn unpend(&mut self, ev: Event) {
+        match ev {
+            Event::AlarmA => _ = self.rb.scr().write(|w| w.calraf().set_bit()),
+            Event::Timestamp => _ = self.rb.scr().write(|w| w.ctsf().set_bit()),
+        }
     }
 
     pub fn enable_calibration_output<PIN: RtcOutputPin>(
@@ -321,6 +361,59 @@ impl Rtc {
         todo!();
     }
 
+    /// Reads the backup register `index`
+    ///
+    /// The C071 has 4 backup registers of 16 bits in the PWR block, they are kept in Standby
+    /// but lost on a power loss as there is no VBAT domain. The C011 and C031 have none.
+    #[cfg(feature = "stm32c071")]
+    pub fn read_backup_register(&self, index: usize) -> u16 {
+        let pwr = unsafe { &*crate::stm32::PWR::ptr() };
+        match index {
+            0 => pwr.bkp0r().read().bkp().bits(),
+            1 => pwr.bkp1r().read().bkp().bits(),
+            2 => pwr.bkp2r().read().bkp().bits(),
+            3 => pwr.bkp3r().read().bkp().bits(),
+            _ => panic!("backup register index out of range"),
+        }
+    }
+
+    /// Writes the backup register `index`, see [`Self::read_backup_register()`]
+    ///
+    /// The registers are not write protected on the C0, there is no DBP bit to set.
+    #[cfg(feature = "stm32c071")]
+    pub fn write_backup_register(&mut self, index: usize, value: u16) {
+        let pwr = unsafe { &*crate::stm32::PWR::ptr() };
+        match index {
+            0 => pwr.bkp0r().write(|w| unsafe { w.bkp().bits(value) }),
+            1 => pwr.bkp1r().write(|w| unsafe { w.bkp().bits(value) }),
+            2 => pwr.bkp2r().write(|w| unsafe { w.bkp().bits(value) }),
+            3 => pwr.bkp3r().write(|w| unsafe { w.bkp().bits(value) }),
+            _ => panic!("backup register index out of range"),
+        };
+    }
+
+    /// Writes `value` to the backup register `index` and requests a system reset
+    ///
+    /// The backup registers survive the reset, which lets the application pick up a flag
+    /// (e.g. a bootloader request) on the next boot with [`Self::read_backup_register()`].
+    #[cfg(feature = "stm32c071")]
+    pub fn reset_with_backup_flag(&mut self, index: usize, value: u16) -> ! {
+        self.write_backup_register(index, value);
+        cortex_m::peripheral::SCB::sys_reset()
+    }
+
+    fn unlocked<F>(&mut self, closure: F)
+    where
+        F: FnOnce(&mut RTC),
+    {
+        // Disable write protection
+        self.rb.wpr().write(|w| unsafe { w.bits(0xCA) });
+        self.rb.wpr().write(|w| unsafe { w.bits(0x53) });
+        closure(&mut self.rb);
+        // Enable_write_protection
+        self.rb.wpr().write(|w| unsafe { w.bits(0xFF) });
+    }
+
     fn modify<F>(&mut self, mut closure: F)
     where
         F: FnMut(&mut RTC),
@@ -332,13 +425,14 @@ impl Rtc {
         let isr = self.rb.icsr().read();
         if isr.initf().bit_is_clear() {
             self.rb.icsr().write(|w| w.init().set_bit());
-            self.rb.icsr().write(|w| unsafe { w.bits(0xFFFF_FFFF) });
             while self.rb.icsr().read().initf().bit_is_clear() {}
         }
         // Invoke closure
         closure(&mut self.rb);
-        // Exit init mode
-        self.rb.icsr().write(|w| w.init().clear_bit());
+        // Exit init mode, RSF is set again once the shadow registers hold the new calendar
+        self.rb
+            .icsr()
+            .write(|w| w.init().clear_bit().rsf().clear_bit());
         // Enable_write_protection
         self.rb.wpr().write(|w| unsafe { w.bits(0xFF) });
     }
@@ -386,18 +480,28 @@ rtc_out_pins! {
 }
 
 fn bcd2_encode(word: u32) -> (u8, u8) {
-    let mut value = word as u8;
-    let mut bcd_high: u8 = 0;
-    while value >= 10 {
-        bcd_high += 1;
-        value -= 10;
-    }
-    let bcd_low = (bcd_high << 4) | value;
-    (bcd_high, bcd_low)
+    let value = word as u8;
+    (value / 10, value % 10)
 }
 
 fn bcd2_decode(fst: u8, snd: u8) -> u32 {
-    let value = snd | fst << 4;
-    let value = (value & 0x0F) + ((value & 0xF0) >> 4) * 10;
-    value as u32
+    fst as u32 * 10 + snd as u32
+}
+
+/// ISO week day of `date`, 1 for Monday to 7 for Sunday
+fn week_day(date: &Date) -> u8 {
+    const OFFSETS: [u32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let year = if date.month < 3 {
+        date.year - 1
+    } else {
+        date.year
+    };
+    let day =
+        (year + year / 4 - year / 100 + year / 400 + OFFSETS[date.month as usize - 1] + date.day)
+            % 7;
+    if day == 0 {
+        7
+    } else {
+        day as u8
+    }
 }
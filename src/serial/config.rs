@@ -1,6 +1,9 @@
 use crate::prelude::*;
 use crate::time::Bps;
 
+/// Number of data bits, a parity bit is sent on top of them
+///
+/// The USART frames hold at most 9 bits, 9 data bits can't be used with parity.
 #[derive(Eq, PartialEq, PartialOrd, Clone, Copy)]
 pub enum WordLength {
     DataBits7,
@@ -33,6 +36,15 @@ impl StopBits {
     }
 }
 
+/// Hardware flow control
+#[derive(Eq, PartialEq, PartialOrd, Clone, Copy, Debug)]
+pub enum FlowControl {
+    /// No flow control
+    None,
+    /// RTS and CTS, the pins are passed as `(tx, rx, rts, cts)`
+    RtsCts,
+}
+
 #[derive(Eq, PartialEq, PartialOrd, Clone, Copy, Debug)]
 pub enum FifoThreshold {
     #[doc = "1/8 of its depth"]
@@ -62,6 +74,8 @@ pub struct Config {
     pub(crate) parity: Parity,
     pub(crate) stopbits: StopBits,
     pub(crate) swap: bool,
+    pub(crate) flow_control: FlowControl,
+    pub(crate) half_duplex: bool,
     pub(crate) fifo_enable: bool,
     pub(crate) tx_fifo_threshold: FifoThreshold,
     pub(crate) rx_fifo_threshold: FifoThreshold,
@@ -92,6 +106,11 @@ impl Config {
         self
     }
 
+    pub fn wordlength_7(mut self) -> Self {
+        self.wordlength = WordLength::DataBits7;
+        self
+    }
+
     pub fn wordlength_8(mut self) -> Self {
         self.wordlength = WordLength::DataBits8;
         self
@@ -115,6 +134,25 @@ impl Config {
         self
     }
 
+    /// Selects the hardware flow control
+    ///
+    /// The constructor fails with `InvalidConfig` unless the RTS and CTS pins are passed along
+    /// with `FlowControl::RtsCts`.
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    /// Single-wire half-duplex mode
+    ///
+    /// Data is sent and received on the TX pin, pass the pins as `(tx, NoRx)` with `tx` in
+    /// open-drain mode (`into_open_drain_output()`). The line must be pulled up externally,
+    /// the internal pull-up is too weak for most baud rates.
+    pub fn half_duplex(mut self) -> Self {
+        self.half_duplex = true;
+        self
+    }
+
     pub fn fifo_enable(mut self) -> Self {
         self.fifo_enable = true;
         self
@@ -160,6 +198,8 @@ impl Default for Config {
             parity: Parity::ParityNone,
             stopbits: StopBits::STOP1,
             swap: false,
+            flow_control: FlowControl::None,
+            half_duplex: false,
             fifo_enable: false,
             tx_fifo_threshold: FifoThreshold::FIFO_8_BYTES,
             rx_fifo_threshold: FifoThreshold::FIFO_8_BYTES,
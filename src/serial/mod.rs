@@ -1,3 +1,7 @@
+//! Serial communication using the USART peripherals
+//!
+//! `Serial`, `Tx` and `Rx` implement the embedded-hal 0.2 `nb` serial traits and
+//! `core::fmt::Write`.
 pub mod config;
 pub mod usart;
 
@@ -1,6 +1,7 @@
 use core::fmt;
 use core::marker::PhantomData;
 
+use crate::dma::{self, Channel, Direction, DmaMuxIndex, WordSize};
 use crate::gpio::{AltFunction, *};
 use crate::prelude::*;
 use crate::rcc::*;
@@ -11,7 +12,7 @@ use crate::stm32::*;
 use nb::block;
 
 /// Serial error
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     /// Framing error
     Framing,
@@ -21,6 +22,8 @@ pub enum Error {
     Overrun,
     /// Parity check error
     Parity,
+    /// The DMA channel reported a transfer error
+    Dma,
 }
 
 /// Interrupt event
@@ -87,6 +90,33 @@ pub struct Serial<USART> {
     usart: USART,
 }
 
+/// A DMA transmission in progress, `release()` gives back the transmitter, the channel and
+/// the buffer
+pub struct TxTransfer<TX, CH, BUF> {
+    tx: TX,
+    channel: CH,
+    buffer: BUF,
+    done: bool,
+}
+
+/// A circular DMA reception filling a ring buffer
+///
+/// The channel keeps overwriting the buffer. When bytes are not drained with
+/// [`read`](Self::read) within one buffer length, the next `read` or `available` reports
+/// `Error::Overrun` and the unread bytes are dropped.
+pub struct RxRingBuffer<RX, CH> {
+    rx: RX,
+    channel: CH,
+    buffer: &'static mut [u8],
+    read_pos: usize,
+    /// Write position seen by the last update
+    write_pos: usize,
+    /// Received bytes not drained yet
+    unread: usize,
+    /// Half and end of buffer crossed without their HT/TC flag showing up yet
+    pending: (bool, bool),
+}
+
 // Serial TX pin
 pub trait TxPin<USART> {
     fn setup(&self);
@@ -124,9 +154,22 @@ pub trait DriverEnablePin<USART> {
     fn release(self) -> Self;
 }
 
+// Request to send pin
+pub trait RtsPin<USART> {
+    fn setup(&self);
+    fn release(self) -> Self;
+}
+
+// Clear to send pin
+pub trait CtsPin<USART> {
+    fn setup(&self);
+    fn release(self) -> Self;
+}
+
 // Serial pins
 pub trait Pins<USART> {
     const DRIVER_ENABLE: bool;
+    const FLOW_CONTROL: bool;
 
     fn setup(&self);
     fn release(self) -> Self;
@@ -139,6 +182,7 @@ where
     RX: RxPin<USART>,
 {
     const DRIVER_ENABLE: bool = false;
+    const FLOW_CONTROL: bool = false;
 
     fn setup(&self) {
         self.0.setup();
@@ -158,6 +202,7 @@ where
     DE: DriverEnablePin<USART>,
 {
     const DRIVER_ENABLE: bool = true;
+    const FLOW_CONTROL: bool = false;
 
     fn setup(&self) {
         self.0.setup();
@@ -170,6 +215,34 @@ where
     }
 }
 
+// Duplex mode with hardware flow control
+impl<USART, TX, RX, RTS, CTS> Pins<USART> for (TX, RX, RTS, CTS)
+where
+    TX: TxPin<USART>,
+    RX: RxPin<USART>,
+    RTS: RtsPin<USART>,
+    CTS: CtsPin<USART>,
+{
+    const DRIVER_ENABLE: bool = false;
+    const FLOW_CONTROL: bool = true;
+
+    fn setup(&self) {
+        self.0.setup();
+        self.1.setup();
+        self.2.setup();
+        self.3.setup();
+    }
+
+    fn release(self) -> Self {
+        (
+            self.0.release(),
+            self.1.release(),
+            self.2.release(),
+            self.3.release(),
+        )
+    }
+}
+
 pub trait SerialExt<USART> {
     fn usart<PINS: Pins<USART>>(
         self,
@@ -211,7 +284,8 @@ macro_rules! uart_shared {
     ($USARTX:ident, $dmamux_rx:ident, $dmamux_tx:ident,
         tx: [ $(($PTX:ident, $TAF:expr),)+ ],
         rx: [ $(($PRX:ident, $RAF:expr),)+ ],
-        de: [ $(($PDE:ident, $DAF:expr),)+ ]) => {
+        de: [ $(($PDE:ident, $DAF:expr),)+ ],
+        cts: [ $(($PCTS:ident, $CAF:expr),)+ ]) => {
 
         $(
             impl<MODE> TxPin<$USARTX> for $PTX<MODE> {
@@ -249,6 +323,31 @@ macro_rules! uart_shared {
             }
         )+
 
+        // RTS shares the pins of DE
+        $(
+            impl<MODE> RtsPin<$USARTX> for $PDE<MODE> {
+                fn setup(&self) {
+                    self.set_alt_mode($DAF)
+                }
+
+                fn release(self) -> Self {
+                    self
+                }
+            }
+        )+
+
+        $(
+            impl<MODE> CtsPin<$USARTX> for $PCTS<MODE> {
+                fn setup(&self) {
+                    self.set_alt_mode($CAF)
+                }
+
+                fn release(self) -> Self {
+                    self
+                }
+            }
+        )+
+
         impl Rx<$USARTX> {
             pub fn listen(&mut self) {
                 let usart = unsafe { &(*$USARTX::ptr()) };
@@ -266,6 +365,30 @@ macro_rules! uart_shared {
                 let usart = unsafe { &(*$USARTX::ptr()) };
                 usart.isr().read().rxfne().bit_is_set()
             }
+
+            /// Starts listening for the idle line interrupt
+            pub fn listen_idle(&mut self) {
+                let usart = unsafe { &(*$USARTX::ptr()) };
+                usart.cr1().modify(|_, w| w.idleie().set_bit());
+            }
+
+            /// Stops listening for the idle line interrupt
+            pub fn unlisten_idle(&mut self) {
+                let usart = unsafe { &(*$USARTX::ptr()) };
+                usart.cr1().modify(|_, w| w.idleie().clear_bit());
+            }
+
+            /// Returns true if an idle line has been detected after a reception
+            pub fn is_idle(&self) -> bool {
+                let usart = unsafe { &(*$USARTX::ptr()) };
+                usart.isr().read().idle().bit_is_set()
+            }
+
+            /// Clears the idle line flag, the interrupt stays pending until it is cleared
+            pub fn clear_idle(&mut self) {
+                let usart = unsafe { &(*$USARTX::ptr()) };
+                usart.icr().write(|w| w.idlecf().bit(true));
+            }
         }
 
         impl hal::serial::Read<u8> for Rx<$USARTX> {
@@ -289,7 +412,16 @@ macro_rules! uart_shared {
                         usart.icr().write(|w| w.orecf().bit(true));
                         nb::Error::Other(Error::Overrun)
                     } else if isr.rxfne().bit_is_set() {
-                        return Ok(usart.rdr().read().bits() as u8)
+                        let byte = usart.rdr().read().bits() as u8;
+                        // with 7 data bits and parity, bit 7 holds the received parity bit
+                        let cr1 = usart.cr1().read();
+                        if cr1.pce().bit_is_set()
+                            && cr1.m0().bit_is_clear()
+                            && cr1.m1().bit_is_clear()
+                        {
+                            return Ok(byte & 0x7f);
+                        }
+                        return Ok(byte)
                     } else {
                         nb::Error::WouldBlock
                     }
@@ -325,6 +457,215 @@ macro_rules! uart_shared {
             }
         }
 
+        impl Tx<$USARTX> {
+            /// Transmits `buffer` through the DMA channel
+            ///
+            /// `buffer` must hold between 1 and 65535 bytes.
+            pub fn write_dma<CH: Channel>(
+                self,
+                mut channel: CH,
+                buffer: &'static [u8],
+            ) -> TxTransfer<Self, CH, &'static [u8]> {
+                let usart = unsafe { &(*$USARTX::ptr()) };
+
+                // TC is cleared before the channel starts feeding TDR
+                usart.icr().write(|w| w.tccf().bit(true));
+                let config = dma::Config::default().request(DmaMuxIndex::$dmamux_tx);
+                dma::setup_channel(
+                    &mut channel,
+                    usart.tdr().as_ptr() as u32,
+                    buffer.as_ptr() as u32,
+                    buffer.len(),
+                    WordSize::BITS8,
+                    Direction::FromMemory,
+                    config,
+                );
+                usart.cr3().modify(|_, w| w.dmat().set_bit());
+
+                TxTransfer { tx: self, channel, buffer, done: false }
+            }
+        }
+
+        impl<CH: Channel> TxTransfer<Tx<$USARTX>, CH, &'static [u8]> {
+            /// Advances the transfer, returns `Ok` once the last byte has left the shift register
+            ///
+            /// A DMA transfer error ends the transfer with `Error::Dma`, the DMA channel is
+            /// stopped in either case.
+            pub fn poll(&mut self) -> nb::Result<(), Error> {
+                if self.done {
+                    return Ok(());
+                }
+
+                let usart = unsafe { &(*$USARTX::ptr()) };
+                if self.channel.is_error() {
+                    self.finish();
+                    Err(nb::Error::Other(Error::Dma))
+                } else if self.channel.is_complete() && usart.isr().read().tc().bit_is_set() {
+                    self.finish();
+                    Ok(())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            /// Blocks until the whole buffer has been transmitted, see [`Self::poll()`]
+            pub fn wait(&mut self) -> Result<(), Error> {
+                nb::block!(self.poll())
+            }
+
+            /// Returns `true` once the transfer is over, successfully or not
+            pub fn is_done(&self) -> bool {
+                self.done
+            }
+
+            /// Gives back the transmitter, the DMA channel and the buffer, a transfer still in
+            /// progress is aborted
+            pub fn release(mut self) -> (Tx<$USARTX>, CH, &'static [u8]) {
+                if !self.done {
+                    self.finish();
+                }
+                (self.tx, self.channel, self.buffer)
+            }
+
+            fn finish(&mut self) {
+                let usart = unsafe { &(*$USARTX::ptr()) };
+                self.channel.disable();
+                self.channel.clear_flags();
+                usart.cr3().modify(|_, w| w.dmat().clear_bit());
+                self.done = true;
+            }
+        }
+
+        impl Rx<$USARTX> {
+            /// Starts a circular DMA reception into `buffer`
+            ///
+            /// `buffer` must hold an even number of bytes between 2 and 65534, the half
+            /// transfer flag is used to detect an overrun.
+            pub fn read_dma<CH: Channel>(
+                self,
+                mut channel: CH,
+                buffer: &'static mut [u8],
+            ) -> RxRingBuffer<Self, CH> {
+                let len = buffer.len();
+                assert!(len > 0 && len % 2 == 0 && len <= u16::MAX as usize);
+
+                let usart = unsafe { &(*$USARTX::ptr()) };
+
+                let config = dma::Config::default()
+                    .request(DmaMuxIndex::$dmamux_rx)
+                    .circular();
+                dma::setup_channel(
+                    &mut channel,
+                    usart.rdr().as_ptr() as u32,
+                    buffer.as_mut_ptr() as u32,
+                    len,
+                    WordSize::BITS8,
+                    Direction::FromPeripheral,
+                    config,
+                );
+
+                usart.icr().write(|w| w.orecf().bit(true));
+                usart.cr3().modify(|_, w| w.dmar().set_bit());
+
+                RxRingBuffer {
+                    rx: self,
+                    channel,
+                    buffer,
+                    read_pos: 0,
+                    write_pos: 0,
+                    unread: 0,
+                    pending: (false, false),
+                }
+            }
+        }
+
+        impl<CH: Channel> RxRingBuffer<Rx<$USARTX>, CH> {
+            /// Accounts for the bytes received since the last call, fails once the DMA has
+            /// lapped the unread bytes
+            fn update(&mut self) -> Result<(), Error> {
+                if self.channel.is_error() {
+                    return Err(Error::Dma);
+                }
+
+                let len = self.buffer.len();
+                let half = len / 2;
+                // The flags are read before NDTR, a boundary crossed in between is carried in
+                // `pending` until its flag shows up
+                let half_flag = self.channel.is_half_complete();
+                let end_flag = self.channel.is_complete();
+                if half_flag {
+                    self.channel.clear_event(dma::Event::HalfTransfer);
+                }
+                if end_flag {
+                    self.channel.clear_event(dma::Event::TransferComplete);
+                }
+                let write_pos = len - self.channel.transfer_remaining() as usize;
+
+                let (last, pos) = (self.write_pos, write_pos);
+                let crossed_end = pos < last;
+                let crossed_half = if crossed_end {
+                    last < half || half <= pos
+                } else {
+                    last < half && half <= pos
+                };
+                // A flag that no crossing since the last update explains means a whole lap
+                let lapped = (half_flag && !crossed_half && !self.pending.0)
+                    || (end_flag && !crossed_end && !self.pending.1);
+                self.pending = (
+                    crossed_half && !half_flag,
+                    crossed_end && !end_flag,
+                );
+
+                self.unread += (pos + len - last) % len;
+                self.write_pos = pos;
+                if lapped || self.unread >= len {
+                    self.read_pos = pos;
+                    self.unread = 0;
+                    return Err(Error::Overrun);
+                }
+                Ok(())
+            }
+
+            /// Returns the number of received bytes not drained yet
+            ///
+            /// Fails with `Error::Overrun` if the DMA overwrote bytes that were not drained,
+            /// they are dropped and counting restarts from the current DMA position.
+            pub fn available(&mut self) -> Result<usize, Error> {
+                self.update()?;
+                Ok(self.unread)
+            }
+
+            /// Moves up to `buf.len()` received bytes into `buf`, returns the number of bytes read
+            ///
+            /// Fails like [`available`](Self::available) on an overrun.
+            pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+                self.update()?;
+                let len = self.buffer.len();
+                let count = self.unread.min(buf.len());
+                let src = self.buffer.as_ptr();
+                for byte in buf.iter_mut().take(count) {
+                    // the DMA writes the buffer behind our back
+                    *byte = unsafe { core::ptr::read_volatile(src.add(self.read_pos)) };
+                    self.read_pos = (self.read_pos + 1) % len;
+                }
+                self.unread -= count;
+                Ok(count)
+            }
+
+            /// Stops the reception and gives back the receiver, the channel and the buffer
+            pub fn release(self) -> (Rx<$USARTX>, CH, &'static mut [u8]) {
+                let RxRingBuffer { rx, mut channel, buffer, .. } = self;
+                let usart = unsafe { &(*$USARTX::ptr()) };
+
+                usart.cr3().modify(|_, w| w.dmar().clear_bit());
+                channel.disable();
+                channel.clear_flags();
+                channel.set_circular_mode(false);
+
+                (rx, channel, buffer)
+            }
+        }
+
         impl hal::serial::Write<u8> for Tx<$USARTX> {
             type Error = Error;
 
@@ -397,15 +738,38 @@ macro_rules! uart {
                 // Enable clock for USART
                 $USARTX::enable(rcc);
 
-                let clk = rcc.clocks.apb_clk.raw() as u64;
+                let flow_control = config.flow_control == FlowControl::RtsCts;
+                if flow_control != PINS::FLOW_CONTROL {
+                    return Err(InvalidConfig);
+                }
+
+                // 16x oversampling: BRR is the kernel clock divided by the baud rate, rounded to
+                // the nearest integer, and must be at least 16
+                let clk = rcc.clocks.apb_clk().raw() as u64;
                 let bdr = config.baudrate.0 as u64;
                 let clk_mul = 1;
-                let div = (clk_mul * clk) / bdr;
-                usart.brr().write(|w| unsafe { w.bits(div as u32) });
+                let div = (clk_mul * clk + bdr / 2) / bdr;
+                if !(16..=0xFFFF).contains(&div) {
+                    return Err(InvalidConfig);
+                }
+
+                // M selects the frame length, which includes the parity bit, so a parity bit is
+                // added on top of the data bits with the next longer frame
+                let parity = config.parity != Parity::ParityNone;
+                let (m0, m1) = match (config.wordlength, parity) {
+                    (WordLength::DataBits7, false) => (false, true),
+                    (WordLength::DataBits7, true) | (WordLength::DataBits8, false) => {
+                        (false, false)
+                    }
+                    (WordLength::DataBits8, true) | (WordLength::DataBits9, false) => (true, false),
+                    (WordLength::DataBits9, true) => return Err(InvalidConfig),
+                };
 
-                // usart.cr1.reset();
+                // BRR is only writable while UE is cleared
+                usart.cr1().reset();
                 usart.cr2().reset();
                 usart.cr3().reset();
+                usart.brr().write(|w| unsafe { w.bits(div as u32) });
 
                 usart.cr2().write(|w| unsafe {
                     w.stop()
@@ -415,7 +779,7 @@ macro_rules! uart {
                 });
 
                 if let Some(timeout) = config.receiver_timeout {
-                    usart.cr1().write(|w| w.rtoie().bit(true));
+                    usart.cr1().modify(|_, w| w.rtoie().bit(true));
                     usart.cr2().modify(|_, w| w.rtoen().bit(true));
                     usart.rtor().write(|w| unsafe { w.rto().bits(timeout) });
                 }
@@ -429,6 +793,14 @@ macro_rules! uart {
                         .bit(config.tx_fifo_interrupt)
                         .rxftie()
                         .bit(config.rx_fifo_interrupt)
+                        .dem()
+                        .bit(PINS::DRIVER_ENABLE)
+                        .rtse()
+                        .bit(flow_control)
+                        .ctse()
+                        .bit(flow_control)
+                        .hdsel()
+                        .bit(config.half_duplex)
                 });
 
                 usart.cr1().modify(|_, w| {
@@ -439,19 +811,17 @@ macro_rules! uart {
                         .re()
                         .bit(true)
                         .m0()
-                        .bit(config.wordlength == WordLength::DataBits7)
+                        .bit(m0)
                         .m1()
-                        .bit(config.wordlength == WordLength::DataBits9)
+                        .bit(m1)
                         .pce()
-                        .bit(config.parity != Parity::ParityNone)
+                        .bit(parity)
                         .ps()
                         .bit(config.parity == Parity::ParityOdd)
                         .fifoen()
                         .bit(config.fifo_enable)
                 });
 
-                usart.cr3().write(|w| w.dem().bit(PINS::DRIVER_ENABLE));
-
                 // Enable pins
                 pins.setup();
 
@@ -491,7 +861,35 @@ macro_rules! uart {
                 (self.usart.isr().read().bits() & event.val()) != 0
             }
 
+            /// Clears the idle line flag
+            pub fn clear_idle(&mut self) {
+                self.usart.icr().write(|w| w.idlecf().bit(true));
+            }
+
+            /// Turns the line around for transmitting in half-duplex mode
+            ///
+            /// The receiver is disabled so the transmitted bytes are not echoed back.
+            pub fn half_duplex_transmit(&mut self) {
+                self.usart
+                    .cr1()
+                    .modify(|_, w| w.re().clear_bit().te().set_bit());
+            }
+
+            /// Turns the line around for receiving in half-duplex mode
+            ///
+            /// Blocks until the last byte has been sent, the transmitter is then disabled so the
+            /// line is released to the other end.
+            pub fn half_duplex_receive(&mut self) {
+                while self.usart.isr().read().tc().bit_is_clear() {}
+                self.usart
+                    .cr1()
+                    .modify(|_, w| w.te().clear_bit().re().set_bit());
+            }
+
             /// Clear pending interrupt
+            ///
+            /// Only the events with a clear bit in ICR are affected, the FIFO and RXNE/TXE flags
+            /// are cleared by accessing the data registers.
             pub fn unpend(&mut self, event: Event) {
                 // mask the allowed bits
                 let mask: u32 = 0x123BFF;
@@ -552,6 +950,10 @@ uart_shared!(USART1, USART1_RX, USART1_TX,
         (PA15, AltFunction::AF4),
         (PB3, AltFunction::AF4),
         (PB6, AltFunction::AF4),
+    ],
+    cts: [
+        (PA11, AltFunction::AF1),
+        (PB4, AltFunction::AF4),
     ]
 );
 
@@ -573,6 +975,9 @@ uart_shared!(USART2, USART2_RX, USART2_TX,
         (PA1, AltFunction::AF1),
         (PB9, AltFunction::AF1),
         (PC14, AltFunction::AF9),
+    ],
+    cts: [
+        (PA0, AltFunction::AF1),
     ]
 );
 
@@ -0,0 +1,26 @@
+//! Device electronic signature
+//!
+//! The unique device ID and the flash size are factory programmed in system memory.
+use core::ptr;
+
+/// Unique device ID, 96 bits
+const UID_BASE: *const u32 = 0x1FFF_7550 as *const u32;
+
+/// Flash size in KiB
+const FLASH_SIZE_BASE: *const u16 = 0x1FFF_75A0 as *const u16;
+
+/// Returns the 96-bit unique device ID, lowest word first
+pub fn uid() -> [u32; 3] {
+    unsafe {
+        [
+            ptr::read_volatile(UID_BASE),
+            ptr::read_volatile(UID_BASE.add(1)),
+            ptr::read_volatile(UID_BASE.add(2)),
+        ]
+    }
+}
+
+/// Returns the size of the main flash in KiB
+pub fn flash_size_kb() -> u16 {
+    unsafe { ptr::read_volatile(FLASH_SIZE_BASE) }
+}
@@ -1,12 +1,26 @@
+use crate::dma::{self, Channel, Direction, DmaMuxIndex, WordSize};
 use crate::gpio::*;
 use crate::rcc::*;
+use crate::stm32::spi1::cr1;
 use crate::stm32::SPI1;
+#[cfg(feature = "stm32c071")]
+use crate::stm32::SPI2;
 use crate::time::Hertz;
-use core::ptr;
+use core::cell::RefCell;
+#[cfg(feature = "async")]
+use core::{
+    future::poll_fn,
+    task::{Context, Poll, Waker},
+};
+use cortex_m::interrupt::{self, Mutex};
+use hal::spi::FullDuplex;
 pub use hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
+use hal_1::delay::DelayNs;
+use hal_1::digital::OutputPin;
+use hal_1::spi::{ErrorKind, Operation};
 
 /// SPI error
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     /// Overrun occurred
     Overrun,
@@ -14,6 +28,43 @@ pub enum Error {
     ModeFault,
     /// CRC error
     Crc,
+    /// Configured data size does not fit the word type
+    DataSize,
+    /// Requested data size is outside of 4 to 16 bits
+    InvalidDataSize,
+    /// The embedded-hal 1.0 bus operation needs a MOSI or MISO pin the SPI was set up
+    /// without
+    MissingPin,
+    /// `flag_selftest` stalled before all the frames went through
+    SelfTest,
+    /// The DMA channel reported a transfer error
+    Dma,
+}
+
+impl hal_1::spi::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Overrun => ErrorKind::Overrun,
+            Error::ModeFault => ErrorKind::ModeFault,
+            Error::Crc
+            | Error::DataSize
+            | Error::InvalidDataSize
+            | Error::MissingPin
+            | Error::SelfTest
+            | Error::Dma => ErrorKind::Other,
+        }
+    }
+}
+
+/// SPI interrupt events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiEvent {
+    /// TX buffer empty
+    Txe,
+    /// RX buffer not empty
+    Rxne,
+    /// Overrun, mode fault, CRC or TI frame format error
+    Error,
 }
 
 /// A filler type for when the SCK pin is unnecessary
@@ -23,7 +74,27 @@ pub struct NoMiso;
 /// A filler type for when the Mosi pin is unnecessary
 pub struct NoMosi;
 
+/// SPI pins, passed as a `(sck, miso, mosi)` or `(sck, miso, mosi, nss)` tuple
+///
+/// The role of a pin, and so its alternate function, is picked by its position in the tuple.
+/// Pins like PB6 that can serve several SPI roles are moved into the tuple, so the same
+/// physical pin can't be set up for two roles at once.
+///
+/// Each `(pin, role)` implementation fixes the alternate function at compile time, and a pin
+/// without an implementation for a role is rejected by the compiler. No C0 pin offers two
+/// alternate functions for the same SPI signal, so the tuple slot is all it takes to select
+/// the AF, there is no remap to pick.
+///
+/// With the `NoMiso` and `NoMosi` fillers the embedded-hal 1.0 `SpiBus` operations that would
+/// need the missing line fail with `Error::MissingPin`: `read` needs MISO, `write` needs MOSI
+/// and `transfer` needs the lines for its non-empty buffers. Dummy frames are still clocked
+/// out by `read` without a MOSI pin. The embedded-hal 0.2 traits don't check the pins.
 pub trait Pins<SPI> {
+    /// `false` when set up with `NoMiso`
+    const MISO: bool = true;
+    /// `false` when set up with `NoMosi`
+    const MOSI: bool = true;
+
     fn setup(&self);
     fn release(self) -> Self;
 }
@@ -34,11 +105,22 @@ pub trait PinSck<SPI> {
 }
 
 pub trait PinMiso<SPI> {
+    /// `false` for the `NoMiso` filler
+    const CONNECTED: bool = true;
+
     fn setup(&self);
     fn release(self) -> Self;
 }
 
 pub trait PinMosi<SPI> {
+    /// `false` for the `NoMosi` filler
+    const CONNECTED: bool = true;
+
+    fn setup(&self);
+    fn release(self) -> Self;
+}
+
+pub trait PinNss<SPI> {
     fn setup(&self);
     fn release(self) -> Self;
 }
@@ -49,6 +131,9 @@ where
     MISO: PinMiso<SPI>,
     MOSI: PinMosi<SPI>,
 {
+    const MISO: bool = MISO::CONNECTED;
+    const MOSI: bool = MOSI::CONNECTED;
+
     fn setup(&self) {
         self.0.setup();
         self.1.setup();
@@ -60,23 +145,325 @@ where
     }
 }
 
+impl<SPI, SCK, MISO, MOSI, NSS> Pins<SPI> for (SCK, MISO, MOSI, NSS)
+where
+    SCK: PinSck<SPI>,
+    MISO: PinMiso<SPI>,
+    MOSI: PinMosi<SPI>,
+    NSS: PinNss<SPI>,
+{
+    const MISO: bool = MISO::CONNECTED;
+    const MOSI: bool = MOSI::CONNECTED;
+
+    fn setup(&self) {
+        self.0.setup();
+        self.1.setup();
+        self.2.setup();
+        self.3.setup();
+    }
+
+    fn release(self) -> Self {
+        (
+            self.0.release(),
+            self.1.release(),
+            self.2.release(),
+            self.3.release(),
+        )
+    }
+}
+
+/// Order in which the bits of a frame are shifted out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+/// NSS management
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NssMode {
+    /// NSS is managed internally, chip selects are driven as GPIOs
+    Software,
+    /// NSS is driven low by the hardware while the SPI is enabled
+    HardwareOutput,
+}
+
+/// SPI master configuration
+#[derive(Clone, Copy)]
+pub struct SpiConfig {
+    pub mode: Mode,
+    pub frequency: Hertz,
+    pub bit_order: BitOrder,
+    /// Frame size in bits, 4 to 16
+    pub data_size: u8,
+    pub nss: NssMode,
+    /// CRC polynomial, `None` leaves the hardware CRC disabled
+    pub crc: Option<u16>,
+}
+
+impl Default for SpiConfig {
+    fn default() -> Self {
+        SpiConfig {
+            mode: MODE_0,
+            frequency: Hertz::MHz(1),
+            bit_order: BitOrder::MsbFirst,
+            data_size: 8,
+            nss: NssMode::Software,
+            crc: None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Spi<SPI, PINS> {
     spi: SPI,
     pins: PINS,
+    word_delay: u32,
+}
+
+/// A DMA transfer in progress, `release()` gives back the SPI, the DMA channel(s) and the
+/// buffer
+pub struct Transfer<SPI, CH, BUF> {
+    spi: SPI,
+    channel: CH,
+    buffer: BUF,
+    done: bool,
+}
+
+fn baud_rate_divider(pclk: Hertz, freq: Hertz) -> u8 {
+    match pclk / freq {
+        0 => unreachable!(),
+        1..=2 => 0b000,
+        3..=5 => 0b001,
+        6..=11 => 0b010,
+        12..=23 => 0b011,
+        24..=47 => 0b100,
+        48..=95 => 0b101,
+        96..=191 => 0b110,
+        _ => 0b111,
+    }
+}
+
+fn clock_mode(w: &mut cr1::W, mode: Mode) -> &mut cr1::W {
+    w.cpha()
+        .bit(mode.phase == Phase::CaptureOnSecondTransition)
+        .cpol()
+        .bit(mode.polarity == Polarity::IdleHigh)
+}
+
+/// Error of a transaction on a device owning its chip-select pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceError<BUS, CS> {
+    /// The bus operation failed
+    Spi(BUS),
+    /// Driving the chip-select pin failed
+    Cs(CS),
+}
+
+impl<BUS, CS> hal_1::spi::Error for DeviceError<BUS, CS>
+where
+    BUS: hal_1::spi::Error,
+    CS: core::fmt::Debug,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DeviceError::Spi(err) => err.kind(),
+            DeviceError::Cs(_) => ErrorKind::ChipSelectFault,
+        }
+    }
+}
+
+/// A bus owning its chip-select pin, CS is held low for the duration of each transaction
+pub struct SpiDevice<BUS, CS, D> {
+    bus: BUS,
+    cs: CS,
+    delay: D,
+}
+
+impl<BUS, CS, D> SpiDevice<BUS, CS, D>
+where
+    CS: OutputPin,
+{
+    /// Wraps `bus`, `cs` is driven high until the first transaction
+    pub fn new(bus: BUS, mut cs: CS, delay: D) -> Result<Self, CS::Error> {
+        cs.set_high()?;
+        Ok(SpiDevice { bus, cs, delay })
+    }
+
+    pub fn release(self) -> (BUS, CS, D) {
+        (self.bus, self.cs, self.delay)
+    }
+}
+
+impl<BUS, CS, D> hal_1::spi::ErrorType for SpiDevice<BUS, CS, D>
+where
+    BUS: hal_1::spi::ErrorType,
+    CS: OutputPin,
+{
+    type Error = DeviceError<BUS::Error, CS::Error>;
+}
+
+impl<BUS, CS, D> hal_1::spi::SpiDevice<u8> for SpiDevice<BUS, CS, D>
+where
+    BUS: hal_1::spi::SpiBus<u8>,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        transaction(&mut self.bus, &mut self.cs, &mut self.delay, operations)
+    }
+}
+
+fn transaction<BUS, CS, D>(
+    bus: &mut BUS,
+    cs: &mut CS,
+    delay: &mut D,
+    operations: &mut [Operation<'_, u8>],
+) -> Result<(), DeviceError<BUS::Error, CS::Error>>
+where
+    BUS: hal_1::spi::SpiBus<u8>,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    cs.set_low().map_err(DeviceError::Cs)?;
+
+    let res = operations.iter_mut().try_for_each(|op| match op {
+        Operation::Read(words) => bus.read(words),
+        Operation::Write(words) => bus.write(words),
+        Operation::Transfer(read, write) => bus.transfer(read, write),
+        Operation::TransferInPlace(words) => bus.transfer_in_place(words),
+        Operation::DelayNs(ns) => {
+            bus.flush()?;
+            delay.delay_ns(*ns);
+            Ok(())
+        }
+    });
+
+    // release CS only once the last frame is out, even if an operation failed
+    let flush = bus.flush();
+    let deselect = cs.set_high();
+
+    res.and(flush).map_err(DeviceError::Spi)?;
+    deselect.map_err(DeviceError::Cs)
+}
+
+/// A bus shared by several devices, each owning its chip-select pin
+///
+/// Every transaction runs in a critical section, so a device can't assert its CS while
+/// another device's transaction is in flight, even from an interrupt handler.
+///
+/// ```
+/// let bus = SharedBus::new(dp.SPI1.spi((sck, miso, mosi), MODE_0, 4.MHz(), &mut rcc));
+/// let cs = gpioa.pa4.into_push_pull_output();
+/// let mut flash = bus.device(cs, cp.SYST.delay(&mut rcc)).unwrap();
+/// let cs = gpiob.pb0.into_push_pull_output();
+/// let mut sensor = bus.device(cs, dp.TIM14.delay(&mut rcc)).unwrap();
+/// ```
+pub struct SharedBus<BUS> {
+    bus: Mutex<RefCell<BUS>>,
+}
+
+impl<BUS> SharedBus<BUS> {
+    pub fn new(bus: BUS) -> Self {
+        SharedBus {
+            bus: Mutex::new(RefCell::new(bus)),
+        }
+    }
+
+    /// Creates a device selected by `cs`, which is driven high until its first transaction
+    pub fn device<CS, D>(
+        &self,
+        mut cs: CS,
+        delay: D,
+    ) -> Result<SharedDevice<'_, BUS, CS, D>, CS::Error>
+    where
+        CS: OutputPin,
+    {
+        cs.set_high()?;
+        Ok(SharedDevice {
+            bus: &self.bus,
+            cs,
+            delay,
+        })
+    }
+}
+
+/// A device on a [`SharedBus`]
+pub struct SharedDevice<'a, BUS, CS, D> {
+    bus: &'a Mutex<RefCell<BUS>>,
+    cs: CS,
+    delay: D,
+}
+
+impl<BUS, CS, D> SharedDevice<'_, BUS, CS, D> {
+    pub fn release(self) -> (CS, D) {
+        (self.cs, self.delay)
+    }
+}
+
+impl<BUS, CS, D> hal_1::spi::ErrorType for SharedDevice<'_, BUS, CS, D>
+where
+    BUS: hal_1::spi::ErrorType,
+    CS: OutputPin,
+{
+    type Error = DeviceError<BUS::Error, CS::Error>;
+}
+
+impl<BUS, CS, D> hal_1::spi::SpiDevice<u8> for SharedDevice<'_, BUS, CS, D>
+where
+    BUS: hal_1::spi::SpiBus<u8>,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        interrupt::free(|cs| {
+            let mut bus = self.bus.borrow(cs).borrow_mut();
+            transaction(&mut *bus, &mut self.cs, &mut self.delay, operations)
+        })
+    }
 }
 
 pub trait SpiExt: Sized {
     fn spi<PINS>(self, pins: PINS, mode: Mode, freq: Hertz, rcc: &mut Rcc) -> Spi<Self, PINS>
     where
         PINS: Pins<Self>;
+
+    /// Fails with `Error::InvalidDataSize` if `config.data_size` is outside of 4 to 16 bits
+    fn spi_with_config<PINS>(
+        self,
+        pins: PINS,
+        config: SpiConfig,
+        rcc: &mut Rcc,
+    ) -> Result<Spi<Self, PINS>, Error>
+    where
+        PINS: Pins<Self>;
+
+    fn spi_slave<PINS>(self, pins: PINS, mode: Mode, rcc: &mut Rcc) -> Spi<Self, PINS>
+    where
+        PINS: Pins<Self>;
+
+    /// Runs a go/no-go check of the SPI status flags without any external hardware
+    ///
+    /// A few dummy frames are clocked through the peripheral as a master with no pins
+    /// connected, checking that every frame leaves the TX FIFO, a frame is received in its
+    /// place and no error flag is raised. The C0 SPI has no internal loopback and MISO isn't
+    /// routed without a pin, so no data is checked, jumper MOSI to MISO and compare a
+    /// `transfer` to check the data path. The peripheral is reset afterwards.
+    fn flag_selftest(&mut self, rcc: &mut Rcc) -> Result<(), Error>;
 }
 
+/// Register polls before the self-test gives up on a flag
+const SELFTEST_TIMEOUT: u32 = 10_000;
+
+/// Dummy frames clocked through the SPI by the self-test
+const SELFTEST_FRAMES: usize = 4;
+
 macro_rules! spi {
-    ($SPIX:ident, $spiX:ident,
+    ($SPIX:ident, $spiX:ident, $spiX_slave:ident, $RX_REQ:ident, $TX_REQ:ident,
         sck: [ $(($SCK:ty, $SCK_AF:expr),)+ ],
         miso: [ $(($MISO:ty, $MISO_AF:expr),)+ ],
         mosi: [ $(($MOSI:ty, $MOSI_AF:expr),)+ ],
+        nss: [ $(($NSS:ty, $NSS_AF:expr),)+ ],
     ) => {
         impl PinSck<$SPIX> for NoSck {
             fn setup(&self) {}
@@ -87,6 +474,8 @@ macro_rules! spi {
         }
 
         impl PinMiso<$SPIX> for NoMiso {
+            const CONNECTED: bool = false;
+
             fn setup(&self) {}
 
             fn release(self) -> Self {
@@ -95,6 +484,8 @@ macro_rules! spi {
         }
 
         impl PinMosi<$SPIX> for NoMosi {
+            const CONNECTED: bool = false;
+
             fn setup(&self) {}
 
             fn release(self) -> Self {
@@ -106,6 +497,7 @@ macro_rules! spi {
             impl PinSck<$SPIX> for $SCK {
                 fn setup(&self) {
                     self.set_alt_mode($SCK_AF);
+                    self.internal_set_speed(Speed::VeryHigh);
                 }
 
                 fn release(self) -> Self {
@@ -128,6 +520,18 @@ macro_rules! spi {
             impl PinMosi<$SPIX> for $MOSI {
                 fn setup(&self) {
                     self.set_alt_mode($MOSI_AF);
+                    self.internal_set_speed(Speed::VeryHigh);
+                }
+
+                fn release(self) -> Self {
+                    self.into_analog()
+                }
+            }
+        )*
+        $(
+            impl PinNss<$SPIX> for $NSS {
+                fn setup(&self) {
+                    self.set_alt_mode($NSS_AF);
                 }
 
                 fn release(self) -> Self {
@@ -136,6 +540,13 @@ macro_rules! spi {
             }
         )*
 
+        impl<SCK, MISO, MOSI, NSS: PinNss<$SPIX>> Spi<$SPIX, (SCK, MISO, MOSI, NSS)> {
+            /// Hands NSS over to the hardware, it is driven low while the SPI is enabled
+            pub fn use_hardware_nss(&mut self) {
+                self.set_nss_output(true);
+            }
+        }
+
         impl<PINS: Pins<$SPIX>> Spi<$SPIX, PINS> {
             pub fn $spiX(
                 spi: $SPIX,
@@ -144,63 +555,151 @@ macro_rules! spi {
                 speed: Hertz,
                 rcc: &mut Rcc
             ) -> Self {
+                let config = SpiConfig {
+                    mode,
+                    frequency: speed,
+                    ..SpiConfig::default()
+                };
+                Self::configure(spi, pins, config, rcc)
+            }
+
+            /// Configures the SPI as a master from `config`
+            ///
+            /// Fails with `Error::InvalidDataSize` if `config.data_size` is outside of 4 to 16
+            /// bits, like `set_data_size`.
+            pub fn with_config(
+                spi: $SPIX,
+                pins: PINS,
+                config: SpiConfig,
+                rcc: &mut Rcc,
+            ) -> Result<Self, Error> {
+                if !(4..=16).contains(&config.data_size) {
+                    return Err(Error::InvalidDataSize);
+                }
+                Ok(Self::configure(spi, pins, config, rcc))
+            }
+
+            fn configure(spi: $SPIX, pins: PINS, config: SpiConfig, rcc: &mut Rcc) -> Self {
                 $SPIX::enable(rcc);
                 $SPIX::reset(rcc);
 
-                // disable SS output
-                spi.cr2().write(|w| w.ssoe().clear_bit());
-
-                let br = match rcc.clocks.apb_clk / speed {
-                    0 => unreachable!(),
-                    1..=2 => 0b000,
-                    3..=5 => 0b001,
-                    6..=11 => 0b010,
-                    12..=23 => 0b011,
-                    24..=47 => 0b100,
-                    48..=95 => 0b101,
-                    96..=191 => 0b110,
-                    _ => 0b111,
-                };
+                let br = baud_rate_divider(rcc.clocks.apb_clk(), config.frequency);
+                let wide = config.data_size > 8;
+                let hardware_nss = config.nss == NssMode::HardwareOutput;
 
                 spi.cr2().write(|w| unsafe {
-                    w.frxth().set_bit().ds().bits(0b111).ssoe().clear_bit()
+                    w.frxth()
+                        .bit(!wide)
+                        .ds()
+                        .bits(config.data_size - 1)
+                        .ssoe()
+                        .bit(hardware_nss)
                 });
 
+                if let Some(polynomial) = config.crc {
+                    spi.crcpr().write(|w| w.crcpoly().set(polynomial));
+                }
+
                 // Enable pins
                 pins.setup();
 
                 spi.cr1().write(|w| unsafe {
-                    w.cpha()
-                        .bit(mode.phase == Phase::CaptureOnSecondTransition)
-                        .cpol()
-                        .bit(mode.polarity == Polarity::IdleHigh)
+                    clock_mode(w, config.mode)
                         .mstr()
                         .set_bit()
                         .br()
                         .bits(br)
                         .lsbfirst()
-                        .clear_bit()
+                        .bit(config.bit_order == BitOrder::LsbFirst)
                         .ssm()
-                        .set_bit()
+                        .bit(!hardware_nss)
                         .ssi()
                         .set_bit()
+                        .crcl()
+                        .bit(wide)
+                        .crcen()
+                        .bit(config.crc.is_some())
                         .rxonly()
                         .clear_bit()
                         .bidimode()
                         .clear_bit()
-                        .ssi()
+                        .spe()
                         .set_bit()
+                });
+
+                Spi {
+                    spi,
+                    pins,
+                    word_delay: 0,
+                }
+            }
+
+            /// Configures the SPI as a slave clocked by the master
+            ///
+            /// NSS is managed in software and the slave is always selected. `send()` preloads
+            /// the byte shifted out on the next frame, `read()` returns the byte clocked in by
+            /// the master.
+            pub fn $spiX_slave(spi: $SPIX, pins: PINS, mode: Mode, rcc: &mut Rcc) -> Self {
+                $SPIX::enable(rcc);
+                $SPIX::reset(rcc);
+
+                spi.cr2().write(|w| unsafe {
+                    w.frxth().set_bit().ds().bits(0b111).ssoe().clear_bit()
+                });
+
+                // Enable pins
+                pins.setup();
+
+                spi.cr1().write(|w| {
+                    clock_mode(w, mode)
+                        .mstr()
+                        .clear_bit()
+                        .lsbfirst()
+                        .clear_bit()
+                        .ssm()
+                        .set_bit()
+                        .ssi()
+                        .clear_bit()
+                        .rxonly()
+                        .clear_bit()
+                        .bidimode()
+                        .clear_bit()
                         .spe()
                         .set_bit()
                 });
 
-                Spi { spi, pins }
+                Spi {
+                    spi,
+                    pins,
+                    word_delay: 0,
+                }
             }
 
-            pub fn data_size(&mut self, nr_bits: u8) {
+            /// Sets the frame size, frames wider than 8 bits are transferred as `u16` words
+            ///
+            /// Frames wider than 8 bits need the half-word RX FIFO threshold, see
+            /// `set_rx_fifo_threshold`.
+            ///
+            /// Only 4 to 16 bits are supported, other sizes return `Error::InvalidDataSize`.
+            pub fn set_data_size(&mut self, nr_bits: u8) -> Result<(), Error> {
+                if !(4..=16).contains(&nr_bits) {
+                    return Err(Error::InvalidDataSize);
+                }
                 self.spi.cr2().modify(|_, w| unsafe {
                     w.ds().bits(nr_bits-1)
                 });
+                Ok(())
+            }
+
+            /// Selects the RX FIFO level that raises RXNE, a half-word (16-bit) or a quarter
+            /// (8-bit) of the FIFO
+            ///
+            /// The constructor selects the 8-bit threshold. Frames wider than 8 bits are read
+            /// as half-words and should use the half-word threshold. With 8-bit frames the
+            /// half-word threshold only raises RXNE once two frames have been received, so a
+            /// lone frame never completes `read()`.
+            pub fn set_rx_fifo_threshold(&mut self, half_word: bool) {
+                self.spi.cr2().modify(|_, w| w.frxth().bit(!half_word));
             }
 
             pub fn half_duplex_enable(&mut self, enable: bool) {
@@ -215,11 +714,517 @@ macro_rules! spi {
                 );
             }
 
-            pub fn release(self) -> ($SPIX, PINS) {
+            /// Turns the single data line to output if needed and sends `byte`
+            ///
+            /// Needs `half_duplex_enable(true)`.
+            pub fn half_duplex_write(&mut self, byte: u8) -> nb::Result<(), Error> {
+                if self.spi.cr1().read().bidioe().bit_is_clear() {
+                    self.set_bidi_output(true);
+                }
+                FullDuplex::<u8>::send(self, byte)
+            }
+
+            /// Turns the single data line to input once the last written frame is out and
+            /// reads a byte
+            ///
+            /// Needs `half_duplex_enable(true)`. The master clocks continuously while the line
+            /// is an input, switch back with `half_duplex_write` or disable the SPI to stop.
+            pub fn half_duplex_read(&mut self) -> nb::Result<u8, Error> {
+                if self.spi.cr1().read().bidioe().bit_is_set() {
+                    let sr = self.spi.sr().read();
+                    if sr.ftlvl().bits() != 0 || sr.bsy().bit_is_set() {
+                        return Err(nb::Error::WouldBlock);
+                    }
+                    self.set_bidi_output(false);
+                }
+                FullDuplex::<u8>::read(self)
+            }
+
+            fn set_bidi_output(&mut self, output: bool) {
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi.cr1().modify(|_, w| w.bidioe().bit(output));
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            fn check_pins(read: bool, write: bool) -> Result<(), Error> {
+                if (read && !PINS::MISO) || (write && !PINS::MOSI) {
+                    Err(Error::MissingPin)
+                } else {
+                    Ok(())
+                }
+            }
+
+            /// Sends the `N` bytes of `data` and returns the bytes received in their place
+            pub fn transfer_array<const N: usize>(
+                &mut self,
+                mut data: [u8; N],
+            ) -> Result<[u8; N], Error> {
+                for word in data.iter_mut() {
+                    *word = self.transfer_word(*word)?;
+                }
+                Ok(data)
+            }
+
+            /// Sends each byte of `buf` and overwrites it with the byte received in its place
+            pub fn transfer_in_place(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+                for word in buf.iter_mut() {
+                    *word = self.transfer_word(*word)?;
+                }
+                Ok(())
+            }
+
+            /// Disables the SPI, see [`Self::disable()`], and releases the peripheral and pins
+            pub fn release(mut self) -> ($SPIX, PINS) {
+                self.disable();
+                (self.spi, self.pins.release())
+            }
+
+            /// Releases the SPI peripheral and pins and gates the peripheral clock off
+            ///
+            /// Re-enable the clock with [`Enable::enable`] and set the peripheral up again to
+            /// reuse it.
+            pub fn release_and_disable(mut self, rcc: &mut Rcc) -> ($SPIX, PINS) {
+                self.disable();
+                $SPIX::disable(rcc);
                 (self.spi, self.pins.release())
             }
         }
 
+        impl<PINS> Spi<$SPIX, PINS> {
+            /// Gives direct access to the SPI registers
+            ///
+            /// # Safety
+            ///
+            /// MSTR, SPE and the DMA enable bits must not be changed while a transfer is
+            /// ongoing, and the configuration the HAL relies on (frame size vs. word type,
+            /// software NSS) must be restored before using the other methods again.
+            pub unsafe fn raw(&mut self) -> &$SPIX {
+                &self.spi
+            }
+
+            /// Clears a pending overrun by reading DR then SR, returns the stale byte
+            pub fn clear_overrun(&mut self) -> u8 {
+                let byte = self.spi.dr8().read().dr().bits();
+                self.spi.sr().read();
+                byte
+            }
+
+            /// Disables the SPI following the reference manual sequence
+            ///
+            /// Waits for the TX FIFO to drain and BSY to clear so the last frame is fully
+            /// clocked out, clears SPE, then empties the RX FIFO. The SPI stays disabled until
+            /// it is set up again.
+            pub fn disable(&mut self) {
+                if self.spi.cr1().read().spe().bit_is_set() {
+                    while self.spi.sr().read().ftlvl().bits() != 0 {}
+                    while self.spi.sr().read().bsy().bit_is_set() {}
+                    self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                }
+                while self.spi.sr().read().frlvl().bits() != 0 {
+                    self.spi.dr8().read();
+                }
+            }
+
+            /// Empties the RX FIFO and returns the number of stale bytes dropped
+            ///
+            /// Mixing `FullDuplex::send` without a matching `read` leaves frames in the RX
+            /// FIFO, they would be returned by the next `read` or overrun the FIFO. The
+            /// blocking and async `write` implementations call this first, `transfer` and `read`
+            /// don't, so call it before those after a bare `send`.
+            pub fn drain_rx(&mut self) -> usize {
+                let mut count = 0;
+                while self.spi.sr().read().frlvl().bits() != 0 {
+                    self.spi.dr8().read();
+                    count += 1;
+                }
+                count
+            }
+
+            /// Returns `true` while a frame is being shifted out or the TX FIFO isn't empty
+            pub fn is_busy(&self) -> bool {
+                self.spi.sr().read().bsy().bit_is_set()
+            }
+
+            /// Returns the RX FIFO level: 0 empty, 1 a quarter, 2 half, 3 full
+            pub fn rx_fifo_level(&self) -> u8 {
+                self.spi.sr().read().frlvl().bits()
+            }
+
+            /// Returns the TX FIFO level: 0 empty, 1 a quarter, 2 half, 3 full
+            pub fn tx_fifo_level(&self) -> u8 {
+                self.spi.sr().read().ftlvl().bits()
+            }
+
+            /// Changes the SCK frequency, must only be called while the bus is idle
+            pub fn set_frequency(&mut self, freq: Hertz, rcc: &Rcc) {
+                let br = baud_rate_divider(rcc.clocks.apb_clk(), freq);
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi.cr1().modify(|_, w| unsafe { w.br().bits(br) });
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Returns the SCK frequency after rounding to the prescaler
+            pub fn frequency(&self, rcc: &Rcc) -> Hertz {
+                let br = self.spi.cr1().read().br().bits();
+                Hertz::from_raw(rcc.clocks.apb_clk().raw() >> (br + 1))
+            }
+
+            /// Changes the clock polarity and phase, must only be called while the bus is idle
+            pub fn set_mode(&mut self, mode: Mode) {
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi.cr1().modify(|_, w| clock_mode(w, mode));
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Selects LSB-first or MSB-first frames, must only be called while the bus is idle
+            pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi
+                    .cr1()
+                    .modify(|_, w| w.lsbfirst().bit(bit_order == BitOrder::LsbFirst));
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Enables the 2-line receive-only mode, the master generates the clock continuously
+            /// while SPE is set
+            ///
+            /// `send()` is a no-op in this mode, call `read()` in a loop to collect the frames
+            /// before the RX FIFO overruns.
+            pub fn rx_only(&mut self, enable: bool) {
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi.cr1().modify(|_, w| w.rxonly().bit(enable));
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Switches between the TI frame format and the Motorola format
+            ///
+            /// In TI mode the NSS pulses are generated by the hardware and the clock polarity
+            /// and phase are fixed, the `Mode` passed to the constructor or `set_mode` has no
+            /// effect.
+            pub fn set_ti_mode(&mut self, enable: bool) {
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi.cr2().modify(|_, w| w.frf().bit(enable));
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Lets the hardware drive NSS low while SPE is set instead of managing NSS
+            /// internally
+            pub fn set_nss_output(&mut self, enable: bool) {
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi.cr2().modify(|_, w| w.ssoe().bit(enable));
+                self.spi.cr1().modify(|_, w| w.ssm().bit(!enable).ssi().set_bit());
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Pulses NSS high between frames, needs the hardware NSS output
+            pub fn set_nss_pulse(&mut self, enable: bool) {
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi.cr2().modify(|_, w| w.nssp().bit(enable));
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Enables the hardware CRC, the CRC length follows the configured frame size
+            ///
+            /// `read()`/`send()` report a mismatching received CRC as `Error::Crc` until
+            /// `clear_crc_error` is called.
+            pub fn enable_crc(&mut self, polynomial: u16) {
+                let wide = self.spi.cr2().read().ds().bits() > 0b0111;
+                self.spi.cr1().modify(|_, w| w.spe().clear_bit());
+                self.spi.crcpr().write(|w| w.crcpoly().set(polynomial));
+                self.spi.cr1().modify(|_, w| w.crcl().bit(wide).crcen().set_bit());
+                self.spi.cr1().modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Sends the CRC after the frame currently written to the TX FIFO
+            pub fn next_transfer_is_crc(&mut self) {
+                self.spi.cr1().modify(|_, w| w.crcnext().set_bit());
+            }
+
+            /// Clears the CRC error flag
+            pub fn clear_crc_error(&mut self) {
+                self.spi.sr().write(|w| w.crcerr().clear());
+            }
+
+            /// Starts listening for an interrupt event
+            pub fn listen(&mut self, event: SpiEvent) {
+                match event {
+                    SpiEvent::Txe => _ = self.spi.cr2().modify(|_, w| w.txeie().set_bit()),
+                    SpiEvent::Rxne => _ = self.spi.cr2().modify(|_, w| w.rxneie().set_bit()),
+                    SpiEvent::Error => _ = self.spi.cr2().modify(|_, w| w.errie().set_bit()),
+                }
+            }
+
+            /// Stop listening for an interrupt event
+            pub fn unlisten(&mut self, event: SpiEvent) {
+                match event {
+                    SpiEvent::Txe => _ = self.spi.cr2().modify(|_, w| w.txeie().clear_bit()),
+                    SpiEvent::Rxne => _ = self.spi.cr2().modify(|_, w| w.rxneie().clear_bit()),
+                    SpiEvent::Error => _ = self.spi.cr2().modify(|_, w| w.errie().clear_bit()),
+                }
+            }
+
+            /// Returns `true` if the flag behind `event` is set
+            pub fn is_event_pending(&self, event: SpiEvent) -> bool {
+                let sr = self.spi.sr().read();
+                match event {
+                    SpiEvent::Txe => sr.txe().bit_is_set(),
+                    SpiEvent::Rxne => sr.rxne().bit_is_set(),
+                    SpiEvent::Error => {
+                        sr.ovr().bit_is_set()
+                            || sr.modf().bit_is_set()
+                            || sr.crcerr().bit_is_set()
+                            || sr.fre().bit_is_set()
+                    }
+                }
+            }
+
+            /// Blocks until TXE is set and BSY is clear
+            pub fn wait_idle(&mut self) {
+                loop {
+                    let sr = self.spi.sr().read();
+                    if sr.txe().bit_is_set() && sr.bsy().bit_is_clear() {
+                        break;
+                    }
+                }
+            }
+
+            /// Clocks a dummy zero byte out and returns the byte received meanwhile
+            ///
+            /// In full-duplex mode `read()` only completes after a `send()`, this pairs both
+            /// for read-only devices wired with `NoMosi`.
+            pub fn read_with_clock(&mut self) -> Result<u8, Error> {
+                self.transfer_word(0)
+            }
+
+            /// Sends each word of `data` most significant byte first, stale and received
+            /// frames are dropped
+            ///
+            /// With 8-bit frames each word is split into its big-endian bytes. With 16-bit
+            /// frames the word is sent as is, an MSB-first frame is big-endian on the wire
+            /// already (this needs the half-word RX FIFO threshold).
+            pub fn write_u16_be(&mut self, data: &[u16]) -> Result<(), Error> {
+                self.drain_rx();
+                if self.spi.cr2().read().ds().bits() > 0b0111 {
+                    for word in data {
+                        nb::block!(FullDuplex::<u16>::send(self, *word))?;
+                        nb::block!(FullDuplex::<u16>::read(self))?;
+                    }
+                } else {
+                    for word in data {
+                        let [high, low] = word.to_be_bytes();
+                        self.transfer_word(high)?;
+                        self.transfer_word(low)?;
+                    }
+                }
+                Ok(())
+            }
+
+            /// Keeps the bus idle for `cycles` SCK periods after each byte of the blocking
+            /// `transfer`/`write` paths, 0 disables the gap
+            ///
+            /// The hardware has no inter-frame delay, the gap is a busy-wait computed from the
+            /// current AHB and SCK frequencies, call it again after changing either.
+            pub fn set_inter_word_delay(&mut self, cycles: u8, rcc: &Rcc) {
+                let sck = self.frequency(rcc).raw();
+                self.word_delay = rcc.clocks.ahb_clk().raw() / sck * cycles as u32;
+            }
+
+            fn transfer_word(&mut self, word: u8) -> Result<u8, Error> {
+                nb::block!(FullDuplex::<u8>::send(self, word))?;
+                let word = nb::block!(FullDuplex::<u8>::read(self))?;
+                if self.word_delay > 0 {
+                    cortex_m::asm::delay(self.word_delay);
+                }
+                Ok(word)
+            }
+
+            /// Blocks until the last frame has been shifted out, fails on a mode fault
+            pub fn flush(&mut self) -> Result<(), Error> {
+                loop {
+                    let sr = self.spi.sr().read();
+                    if sr.modf().bit_is_set() {
+                        return Err(Error::ModeFault);
+                    }
+                    if sr.txe().bit_is_set() && sr.bsy().bit_is_clear() {
+                        return Ok(());
+                    }
+                }
+            }
+
+            /// Writes `buffer` through `DR8` using the DMA channel, received frames are dropped
+            ///
+            /// `buffer` must hold between 1 and 65535 bytes.
+            pub fn write_dma<CH: Channel>(
+                self,
+                mut channel: CH,
+                buffer: &'static [u8],
+            ) -> Transfer<Self, CH, &'static [u8]> {
+                let dr = self.spi.dr8().as_ptr() as u32;
+                let config = dma::Config::default().request(DmaMuxIndex::$TX_REQ);
+                dma::setup_channel(
+                    &mut channel,
+                    dr,
+                    buffer.as_ptr() as u32,
+                    buffer.len(),
+                    WordSize::BITS8,
+                    Direction::FromMemory,
+                    config,
+                );
+
+                self.spi.cr2().modify(|_, w| w.txdmaen().set_bit());
+
+                Transfer { spi: self, channel, buffer, done: false }
+            }
+
+            /// Fills `buffer` from `DR8` using the `rx` DMA channel, the `tx` channel clocks
+            /// out zeros
+            ///
+            /// `buffer` must hold between 1 and 65535 bytes.
+            pub fn read_dma<RX: Channel, TX: Channel>(
+                self,
+                mut rx: RX,
+                mut tx: TX,
+                buffer: &'static mut [u8],
+            ) -> Transfer<Self, (RX, TX), &'static mut [u8]> {
+                static ZERO: u8 = 0;
+
+                let dr = self.spi.dr8().as_ptr() as u32;
+                let len = buffer.len();
+
+                let config = dma::Config::default().request(DmaMuxIndex::$RX_REQ);
+                let memory = buffer.as_mut_ptr() as u32;
+                let dir = Direction::FromPeripheral;
+                dma::setup_channel(&mut rx, dr, memory, len, WordSize::BITS8, dir, config);
+
+                // RXDMAEN has to be set before the TX channel starts clocking frames in
+                self.spi.cr2().modify(|_, w| w.rxdmaen().set_bit());
+
+                let config = dma::Config::default()
+                    .request(DmaMuxIndex::$TX_REQ)
+                    .fixed_memory();
+                let memory = &ZERO as *const u8 as u32;
+                let dir = Direction::FromMemory;
+                dma::setup_channel(&mut tx, dr, memory, len, WordSize::BITS8, dir, config);
+
+                self.spi.cr2().modify(|_, w| w.txdmaen().set_bit());
+
+                Transfer { spi: self, channel: (rx, tx), buffer, done: false }
+            }
+        }
+
+        impl<PINS, CH: Channel> Transfer<Spi<$SPIX, PINS>, CH, &'static [u8]> {
+            /// Advances the transfer, returns `Ok` once the whole buffer went out and the SPI is
+            /// idle
+            ///
+            /// A DMA transfer error ends the transfer with `Error::Dma`, the DMA channel is
+            /// stopped in either case.
+            pub fn poll(&mut self) -> nb::Result<(), Error> {
+                if self.done {
+                    return Ok(());
+                }
+
+                if self.channel.is_error() {
+                    self.finish();
+                    return Err(nb::Error::Other(Error::Dma));
+                }
+                let sr = self.spi.spi.sr().read();
+                if self.channel.is_complete() && sr.ftlvl().bits() == 0 && sr.bsy().bit_is_clear() {
+                    self.finish();
+                    return Ok(());
+                }
+                Err(nb::Error::WouldBlock)
+            }
+
+            /// Blocks until the transfer is over, see [`Self::poll()`]
+            pub fn wait(&mut self) -> Result<(), Error> {
+                nb::block!(self.poll())
+            }
+
+            /// Returns `true` once the transfer is over, successfully or not
+            pub fn is_done(&self) -> bool {
+                self.done
+            }
+
+            /// Gives back the SPI, the DMA channel and the buffer, a transfer still in progress
+            /// is aborted
+            pub fn release(mut self) -> (Spi<$SPIX, PINS>, CH, &'static [u8]) {
+                if !self.done {
+                    self.finish();
+                }
+                (self.spi, self.channel, self.buffer)
+            }
+
+            fn finish(&mut self) {
+                let spi = &self.spi.spi;
+                self.channel.disable();
+                self.channel.clear_flags();
+                spi.cr2().modify(|_, w| w.txdmaen().clear_bit());
+
+                // drop the frames received meanwhile, reading SR afterwards clears OVR
+                while spi.sr().read().frlvl().bits() != 0 {
+                    spi.dr8().read();
+                }
+                spi.sr().read();
+                self.done = true;
+            }
+        }
+
+        impl<PINS, RX: Channel, TX: Channel> Transfer<Spi<$SPIX, PINS>, (RX, TX), &'static mut [u8]> {
+            /// Advances the transfer, returns `Ok` once the whole buffer has been received and
+            /// the SPI is idle
+            ///
+            /// A DMA transfer error on either channel ends the transfer with `Error::Dma`, both
+            /// channels are stopped in either case.
+            pub fn poll(&mut self) -> nb::Result<(), Error> {
+                if self.done {
+                    return Ok(());
+                }
+
+                let (rx, tx) = &self.channel;
+                if rx.is_error() || tx.is_error() {
+                    self.finish();
+                    return Err(nb::Error::Other(Error::Dma));
+                }
+                if rx.is_complete() && self.spi.spi.sr().read().bsy().bit_is_clear() {
+                    self.finish();
+                    return Ok(());
+                }
+                Err(nb::Error::WouldBlock)
+            }
+
+            /// Blocks until the transfer is over, see [`Self::poll()`]
+            pub fn wait(&mut self) -> Result<(), Error> {
+                nb::block!(self.poll())
+            }
+
+            /// Returns `true` once the transfer is over, successfully or not
+            pub fn is_done(&self) -> bool {
+                self.done
+            }
+
+            /// Gives back the SPI, the DMA channels and the buffer, a transfer still in progress
+            /// is aborted
+            pub fn release(mut self) -> (Spi<$SPIX, PINS>, (RX, TX), &'static mut [u8]) {
+                if !self.done {
+                    self.finish();
+                }
+                (self.spi, self.channel, self.buffer)
+            }
+
+            fn finish(&mut self) {
+                let (rx, tx) = &mut self.channel;
+                rx.disable();
+                rx.clear_flags();
+                tx.disable();
+                tx.clear_flags();
+                self.spi
+                    .spi
+                    .cr2()
+                    .modify(|_, w| w.txdmaen().clear_bit().rxdmaen().clear_bit());
+                self.done = true;
+            }
+        }
+
         impl SpiExt for $SPIX {
             fn spi<PINS>(self, pins: PINS, mode: Mode, freq: Hertz, rcc: &mut Rcc) -> Spi<$SPIX, PINS>
             where
@@ -227,9 +1232,77 @@ macro_rules! spi {
             {
                 Spi::$spiX(self, pins, mode, freq, rcc)
             }
+
+            fn spi_with_config<PINS>(
+                self,
+                pins: PINS,
+                config: SpiConfig,
+                rcc: &mut Rcc,
+            ) -> Result<Spi<$SPIX, PINS>, Error>
+            where
+                PINS: Pins<$SPIX>,
+            {
+                Spi::<$SPIX, PINS>::with_config(self, pins, config, rcc)
+            }
+
+            fn spi_slave<PINS>(self, pins: PINS, mode: Mode, rcc: &mut Rcc) -> Spi<$SPIX, PINS>
+            where
+                PINS: Pins<$SPIX>,
+            {
+                Spi::$spiX_slave(self, pins, mode, rcc)
+            }
+
+            fn flag_selftest(&mut self, rcc: &mut Rcc) -> Result<(), Error> {
+                $SPIX::enable(rcc);
+                $SPIX::reset(rcc);
+
+                self.cr2().write(|w| unsafe { w.frxth().set_bit().ds().bits(0b111) });
+                self.cr1().write(|w| unsafe {
+                    w.mstr()
+                        .set_bit()
+                        .br()
+                        .bits(0b000)
+                        .ssm()
+                        .set_bit()
+                        .ssi()
+                        .set_bit()
+                        .spe()
+                        .set_bit()
+                });
+
+                let res = (0..SELFTEST_FRAMES).try_for_each(|_| {
+                    if !(0..SELFTEST_TIMEOUT).any(|_| self.sr().read().txe().bit_is_set()) {
+                        return Err(Error::SelfTest);
+                    }
+                    self.dr8().write(|w| w.dr().set(0));
+                    if !(0..SELFTEST_TIMEOUT).any(|_| self.sr().read().rxne().bit_is_set()) {
+                        return Err(Error::SelfTest);
+                    }
+                    self.dr8().read();
+
+                    let sr = self.sr().read();
+                    if sr.ovr().bit_is_set() {
+                        Err(Error::Overrun)
+                    } else if sr.modf().bit_is_set() {
+                        Err(Error::ModeFault)
+                    } else {
+                        Ok(())
+                    }
+                });
+                let res = res.and_then(|_| {
+                    if (0..SELFTEST_TIMEOUT).any(|_| self.sr().read().bsy().bit_is_clear()) {
+                        Ok(())
+                    } else {
+                        Err(Error::SelfTest)
+                    }
+                });
+
+                $SPIX::reset(rcc);
+                res
+            }
         }
 
-        impl<PINS> hal::spi::FullDuplex<u8> for Spi<$SPIX, PINS> {
+        impl<PINS> FullDuplex<u8> for Spi<$SPIX, PINS> {
             type Error = Error;
 
             fn read(&mut self) -> nb::Result<u8, Error> {
@@ -242,17 +1315,19 @@ macro_rules! spi {
                 } else if sr.crcerr().bit_is_set() {
                     nb::Error::Other(Error::Crc)
                 } else if sr.rxne().bit_is_set() {
-                    // NOTE(read_volatile) read only 1 byte (the svd2rust API only allows
-                    // reading a half-word)
-                    return Ok(unsafe {
-                        ptr::read_volatile(&self.spi.dr() as *const _ as *const u8)
-                    });
+                    // NOTE read only 1 byte through DR8, a half-word access would pop two
+                    // frames from the RX FIFO
+                    return Ok(self.spi.dr8().read().dr().bits());
                 } else {
                     nb::Error::WouldBlock
                 })
             }
 
             fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
+                if self.spi.cr1().read().rxonly().bit_is_set() {
+                    return Ok(());
+                }
+
                 let sr = self.spi.sr().read();
 
                 Err(if sr.ovr().bit_is_set() {
@@ -262,9 +1337,9 @@ macro_rules! spi {
                 } else if sr.crcerr().bit_is_set() {
                     nb::Error::Other(Error::Crc)
                 } else if sr.txe().bit_is_set() {
-                    unsafe {
-                        self.spi.dr().write(|w| w.bits(byte as _));
-                    }
+                    // NOTE write only 1 byte through DR8, a half-word access would push two
+                    // frames into the TX FIFO
+                    self.spi.dr8().write(|w| w.dr().set(byte));
                     return Ok(());
                 } else {
                     nb::Error::WouldBlock
@@ -272,19 +1347,251 @@ macro_rules! spi {
             }
         }
 
-        impl<PINS> ::hal::blocking::spi::transfer::Default<u8> for Spi<$SPIX, PINS> {}
+        impl<PINS> FullDuplex<u16> for Spi<$SPIX, PINS> {
+            type Error = Error;
+
+            fn read(&mut self) -> nb::Result<u16, Error> {
+                if self.spi.cr2().read().ds().bits() < 0b1000 {
+                    return Err(nb::Error::Other(Error::DataSize));
+                }
+
+                let sr = self.spi.sr().read();
+
+                Err(if sr.ovr().bit_is_set() {
+                    nb::Error::Other(Error::Overrun)
+                } else if sr.modf().bit_is_set() {
+                    nb::Error::Other(Error::ModeFault)
+                } else if sr.crcerr().bit_is_set() {
+                    nb::Error::Other(Error::Crc)
+                } else if sr.rxne().bit_is_set() {
+                    return Ok(self.spi.dr().read().dr().bits());
+                } else {
+                    nb::Error::WouldBlock
+                })
+            }
+
+            fn send(&mut self, word: u16) -> nb::Result<(), Error> {
+                if self.spi.cr2().read().ds().bits() < 0b1000 {
+                    return Err(nb::Error::Other(Error::DataSize));
+                }
+                if self.spi.cr1().read().rxonly().bit_is_set() {
+                    return Ok(());
+                }
+
+                let sr = self.spi.sr().read();
+
+                Err(if sr.ovr().bit_is_set() {
+                    nb::Error::Other(Error::Overrun)
+                } else if sr.modf().bit_is_set() {
+                    nb::Error::Other(Error::ModeFault)
+                } else if sr.crcerr().bit_is_set() {
+                    nb::Error::Other(Error::Crc)
+                } else if sr.txe().bit_is_set() {
+                    self.spi.dr().write(|w| w.dr().set(word));
+                    return Ok(());
+                } else {
+                    nb::Error::WouldBlock
+                })
+            }
+        }
+
+        impl<PINS> hal_1::spi::ErrorType for Spi<$SPIX, PINS> {
+            type Error = Error;
+        }
+
+        impl<PINS: Pins<$SPIX>> hal_1::spi::SpiBus<u8> for Spi<$SPIX, PINS> {
+            fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+                Self::check_pins(!words.is_empty(), false)?;
+                for word in words.iter_mut() {
+                    *word = self.transfer_word(0)?;
+                }
+                Ok(())
+            }
+
+            fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+                Self::check_pins(false, !words.is_empty())?;
+                self.drain_rx();
+                for word in words {
+                    self.transfer_word(*word)?;
+                }
+                Ok(())
+            }
+
+            /// Clocks `max(read.len(), write.len())` frames
+            ///
+            /// Zeros are sent once `write` runs out, and the frames received past the end of
+            /// `read` are dropped.
+            fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+                Self::check_pins(!read.is_empty(), !write.is_empty())?;
+                let len = read.len().max(write.len());
+                for idx in 0..len {
+                    let word = write.get(idx).copied().unwrap_or(0);
+                    let word = self.transfer_word(word)?;
+                    if let Some(dst) = read.get_mut(idx) {
+                        *dst = word;
+                    }
+                }
+                Ok(())
+            }
+
+            fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Error> {
+                Self::check_pins(!words.is_empty(), !words.is_empty())?;
+                for word in words.iter_mut() {
+                    *word = self.transfer_word(*word)?;
+                }
+                Ok(())
+            }
+
+            fn flush(&mut self) -> Result<(), Error> {
+                Spi::<$SPIX, PINS>::flush(self)
+            }
+        }
+
+        #[cfg(feature = "async")]
+        impl<PINS> Spi<$SPIX, PINS> {
+            fn waker() -> &'static Mutex<RefCell<Option<Waker>>> {
+                static WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
+                &WAKER
+            }
+
+            /// Wakes the task awaiting the SPI peripheral
+            ///
+            /// Must be called from the SPI interrupt handler. The `PINS` parameter is not used,
+            /// so it can be called as `Spi::<SPI1, ()>::on_interrupt()`.
+            pub fn on_interrupt() {
+                let spi = unsafe { &(*$SPIX::ptr()) };
+                spi.cr2().modify(|_, w| {
+                    w.txeie().clear_bit().rxneie().clear_bit().errie().clear_bit()
+                });
+                interrupt::free(|cs| {
+                    if let Some(waker) = Self::waker().borrow(cs).borrow_mut().take() {
+                        waker.wake();
+                    }
+                });
+            }
+
+            fn poll_word<T>(
+                &mut self,
+                cx: &mut Context<'_>,
+                res: nb::Result<T, Error>,
+                rx: bool,
+            ) -> Poll<Result<T, Error>> {
+                match res {
+                    Ok(word) => Poll::Ready(Ok(word)),
+                    Err(nb::Error::Other(err)) => Poll::Ready(Err(err)),
+                    Err(nb::Error::WouldBlock) => {
+                        interrupt::free(|cs| {
+                            Self::waker().borrow(cs).replace(Some(cx.waker().clone()));
+                        });
+                        self.spi.cr2().modify(|_, w| {
+                            w.errie().set_bit().txeie().bit(!rx).rxneie().bit(rx)
+                        });
+                        Poll::Pending
+                    }
+                }
+            }
+
+            async fn exchange(&mut self, word: u8) -> Result<u8, Error> {
+                poll_fn(|cx| {
+                    let res = FullDuplex::<u8>::send(self, word);
+                    self.poll_word(cx, res, false)
+                })
+                .await?;
+                poll_fn(|cx| {
+                    let res = FullDuplex::<u8>::read(self);
+                    self.poll_word(cx, res, true)
+                })
+                .await
+            }
+        }
+
+        #[cfg(feature = "async")]
+        impl<PINS: Pins<$SPIX>> hal_async::spi::SpiBus<u8> for Spi<$SPIX, PINS> {
+            async fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+                Self::check_pins(!words.is_empty(), false)?;
+                for word in words.iter_mut() {
+                    *word = self.exchange(0).await?;
+                }
+                Ok(())
+            }
 
-        impl<PINS> ::hal::blocking::spi::write::Default<u8> for Spi<$SPIX, PINS> {}
+            async fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+                Self::check_pins(false, !words.is_empty())?;
+                self.drain_rx();
+                for word in words {
+                    self.exchange(*word).await?;
+                }
+                Ok(())
+            }
+
+            /// Same as the blocking `transfer`, zeros are sent once `write` runs out and the
+            /// frames past the end of `read` are dropped
+            async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+                Self::check_pins(!read.is_empty(), !write.is_empty())?;
+                let len = read.len().max(write.len());
+                for idx in 0..len {
+                    let word = self.exchange(write.get(idx).copied().unwrap_or(0)).await?;
+                    if let Some(dst) = read.get_mut(idx) {
+                        *dst = word;
+                    }
+                }
+                Ok(())
+            }
+
+            async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Error> {
+                Self::check_pins(!words.is_empty(), !words.is_empty())?;
+                for word in words.iter_mut() {
+                    *word = self.exchange(*word).await?;
+                }
+                Ok(())
+            }
+
+            async fn flush(&mut self) -> Result<(), Error> {
+                // BSY has no interrupt, it clears within a frame once the FIFO is drained
+                Spi::<$SPIX, PINS>::flush(self)
+            }
+        }
+
+        impl<PINS> ::hal::blocking::spi::Transfer<u8> for Spi<$SPIX, PINS> {
+            type Error = Error;
+
+            fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+                for word in words.iter_mut() {
+                    *word = self.transfer_word(*word)?;
+                }
+                Ok(words)
+            }
+        }
+
+        impl<PINS> ::hal::blocking::spi::Write<u8> for Spi<$SPIX, PINS> {
+            type Error = Error;
+
+            fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+                self.drain_rx();
+                for word in words {
+                    self.transfer_word(*word)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl<PINS> ::hal::blocking::spi::transfer::Default<u16> for Spi<$SPIX, PINS> {}
+
+        impl<PINS> ::hal::blocking::spi::write::Default<u16> for Spi<$SPIX, PINS> {}
     }
 }
 
 spi!(
     SPI1,
     spi1,
+    spi1_slave,
+    SPI1_RX,
+    SPI1_TX,
     sck: [
         (PA1<DefaultMode>, AltFunction::AF0),
         (PA5<DefaultMode>, AltFunction::AF0),
         (PB3<DefaultMode>, AltFunction::AF0),
+        // PB6 routes a different SPI1 signal on each AF, the tuple slot selects which
         (PB6<DefaultMode>, AltFunction::AF10),
     ],
     miso: [
@@ -300,4 +1607,41 @@ spi!(
         (PB5<DefaultMode>, AltFunction::AF0),
         (PB6<DefaultMode>, AltFunction::AF8),
     ],
+    nss: [
+        (PA4<DefaultMode>, AltFunction::AF0),
+        (PA15<DefaultMode>, AltFunction::AF0),
+        (PB0<DefaultMode>, AltFunction::AF0),
+    ],
+);
+
+#[cfg(feature = "stm32c071")]
+spi!(
+    SPI2,
+    spi2,
+    spi2_slave,
+    SPI2_RX,
+    SPI2_TX,
+    sck: [
+        (PA0<DefaultMode>, AltFunction::AF0),
+        (PB8<DefaultMode>, AltFunction::AF1),
+        (PB10<DefaultMode>, AltFunction::AF5),
+        (PB13<DefaultMode>, AltFunction::AF0),
+    ],
+    miso: [
+        (PA3<DefaultMode>, AltFunction::AF0),
+        (PA9<DefaultMode>, AltFunction::AF4),
+        (PB2<DefaultMode>, AltFunction::AF1),
+        (PB14<DefaultMode>, AltFunction::AF0),
+    ],
+    mosi: [
+        (PA4<DefaultMode>, AltFunction::AF1),
+        (PA10<DefaultMode>, AltFunction::AF5),
+        (PB7<DefaultMode>, AltFunction::AF1),
+        (PB11<DefaultMode>, AltFunction::AF0),
+        (PB15<DefaultMode>, AltFunction::AF0),
+    ],
+    nss: [
+        (PB9<DefaultMode>, AltFunction::AF5),
+        (PB12<DefaultMode>, AltFunction::AF0),
+    ],
 );
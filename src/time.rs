@@ -1,6 +1,15 @@
+//! Time units
+//!
+//! The rates and durations are `fugit` types: `Hertz` divides and multiplies by integers,
+//! `Hertz / Hertz` gives the ratio, and `KiloHertz`/`MegaHertz` convert into `Hertz` with `into()`.
+//!
+//! The prelude brings the integer extension traits in scope: `RateExtU32` for `1.MHz()`,
+//! `400.kHz()` and `32_768.Hz()`, `ExtU32` for durations like `10.millis()`, and [`U32Ext`]
+//! for `115_200.bps()` and the calendar units.
 pub use fugit::{
-    ExtU32, HertzU32 as Hertz, HoursDurationU32 as Hour, MicrosDurationU32 as MicroSecond,
-    MinutesDurationU32 as Minute, RateExtU32, SecsDurationU32 as Second,
+    ExtU32, HertzU32 as Hertz, HoursDurationU32 as Hour, KilohertzU32 as KiloHertz,
+    MegahertzU32 as MegaHertz, MicrosDurationU32 as MicroSecond, MinutesDurationU32 as Minute,
+    NanosDurationU32 as NanoSecond, RateExtU32, SecsDurationU32 as Second,
 };
 
 /// Baudrate
@@ -98,6 +107,12 @@ impl U32Ext for u32 {
     }
 }
 
+/// Period of one cycle at `hz`, rounded down to the nanosecond
+pub fn period(hz: Hertz) -> NanoSecond {
+    assert!(hz.raw() > 0);
+    NanoSecond::from_ticks(1_000_000_000 / hz.raw())
+}
+
 pub fn duration(hz: Hertz, cycles: u32) -> MicroSecond {
     let cycles = cycles as u64;
     let clk = hz.raw() as u64;
@@ -0,0 +1,196 @@
+//! Input capture
+use crate::gpio::SignalEdge;
+use crate::rcc::*;
+use crate::stm32::*;
+use crate::time::Hertz;
+use crate::timer::pins::TimerPin;
+use crate::timer::*;
+
+/// Timer channel latching the counter on an input edge
+pub struct InputCapture<TIM, PIN> {
+    clk: Hertz,
+    tim: TIM,
+    pin: PIN,
+}
+
+/// Capture/compare channel usable for input capture on `TIM`
+pub trait CaptureChannel<TIM> {
+    fn configure(tim: &TIM, edge: SignalEdge, filter: u8);
+    fn is_captured(tim: &TIM) -> bool;
+    fn read(tim: &TIM) -> u16;
+    fn is_overcaptured(tim: &TIM) -> bool;
+    fn clear_overcapture(tim: &TIM);
+    fn listen(tim: &TIM, enable: bool);
+}
+
+pub trait InputCaptureExt<TIM>: Sized {
+    fn input_capture<PIN>(
+        self,
+        pin: PIN,
+        edge: SignalEdge,
+        rcc: &mut Rcc,
+    ) -> InputCapture<TIM, PIN>
+    where
+        PIN: TimerPin<TIM>,
+        PIN::Channel: CaptureChannel<TIM>;
+}
+
+macro_rules! capture_channels {
+    ($($TIMX:ident: ($CH:ty, $ccmrx_input:ident, $ccxs:ident, $icxf:ident, $ccxe:ident, $ccxp:ident,
+        $ccxnp:ident, $ccxie:ident, $ccxif:ident, $ccxof:ident, $ccrx:ident),)+) => {
+        $(
+            impl CaptureChannel<$TIMX> for $CH {
+                fn configure(tim: &$TIMX, edge: SignalEdge, filter: u8) {
+                    let (ccp, ccnp) = match edge {
+                        SignalEdge::Rising => (false, false),
+                        SignalEdge::Falling => (true, false),
+                        SignalEdge::All => (true, true),
+                    };
+                    tim.ccer().modify(|_, w| w.$ccxe().clear_bit());
+                    tim.$ccmrx_input()
+                        .modify(|_, w| unsafe { w.$ccxs().bits(0b01).$icxf().bits(filter) });
+                    tim.ccer()
+                        .modify(|_, w| w.$ccxp().bit(ccp).$ccxnp().bit(ccnp).$ccxe().set_bit());
+                }
+
+                fn is_captured(tim: &$TIMX) -> bool {
+                    tim.sr().read().$ccxif().bit_is_set()
+                }
+
+                fn read(tim: &$TIMX) -> u16 {
+                    tim.$ccrx().read().bits() as u16
+                }
+
+                fn is_overcaptured(tim: &$TIMX) -> bool {
+                    tim.sr().read().$ccxof().bit_is_set()
+                }
+
+                fn clear_overcapture(tim: &$TIMX) {
+                    tim.sr().modify(|_, w| w.$ccxof().clear_bit());
+                }
+
+                fn listen(tim: &$TIMX, enable: bool) {
+                    tim.dier().modify(|_, w| w.$ccxie().bit(enable));
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! input_capture {
+    ($($TIMX:ident: $timX:ident,)+) => {
+        $(
+            impl<PIN> InputCapture<$TIMX, PIN>
+            where
+                PIN: TimerPin<$TIMX>,
+                PIN::Channel: CaptureChannel<$TIMX>,
+            {
+                /// Starts a free-running counter and captures it on `edge` of `pin`
+                pub fn $timX(tim: $TIMX, pin: PIN, edge: SignalEdge, rcc: &mut Rcc) -> Self {
+                    Self::with_filter(tim, pin, edge, 0, rcc)
+                }
+
+                /// Same as the plain constructor, with the ICxF digital filter set to `filter`
+                pub fn with_filter(
+                    tim: $TIMX,
+                    pin: PIN,
+                    edge: SignalEdge,
+                    filter: u8,
+                    rcc: &mut Rcc,
+                ) -> Self {
+                    assert!(filter < 16);
+                    $TIMX::enable(rcc);
+                    $TIMX::reset(rcc);
+
+                    pin.setup();
+                    PIN::Channel::configure(&tim, edge, filter);
+                    tim.arr().write(|w| unsafe { w.bits(0xffff) });
+                    tim.cr1().modify(|_, w| w.cen().set_bit());
+
+                    InputCapture {
+                        clk: rcc.clocks.apb_tim_clk(),
+                        tim,
+                        pin,
+                    }
+                }
+
+                /// Sets the counter prescaler, the counter runs at the timer clock / (psc + 1)
+                pub fn set_prescaler(&mut self, psc: u16) {
+                    self.tim.psc().write(|w| unsafe { w.psc().bits(psc) });
+                    self.tim.egr().write(|w| w.ug().set_bit());
+                }
+
+                /// Frequency the captured counter values are expressed in
+                pub fn counter_clock(&self) -> Hertz {
+                    self.clk / (self.tim.psc().read().bits() as u32 + 1)
+                }
+
+                /// Returns the latched counter value if an edge was captured since the last read
+                ///
+                /// Reading the value clears the capture flag.
+                pub fn capture(&mut self) -> Option<u16> {
+                    if PIN::Channel::is_captured(&self.tim) {
+                        Some(PIN::Channel::read(&self.tim))
+                    } else {
+                        None
+                    }
+                }
+
+                /// Returns `true` if an edge was captured while the previous one was unread
+                pub fn is_overcapture(&self) -> bool {
+                    PIN::Channel::is_overcaptured(&self.tim)
+                }
+
+                pub fn clear_overcapture(&mut self) {
+                    PIN::Channel::clear_overcapture(&self.tim);
+                }
+
+                /// Starts listening for the capture event
+                pub fn listen(&mut self) {
+                    PIN::Channel::listen(&self.tim, true);
+                }
+
+                /// Stops listening for the capture event
+                pub fn unlisten(&mut self) {
+                    PIN::Channel::listen(&self.tim, false);
+                }
+
+                pub fn release(self) -> ($TIMX, PIN) {
+                    self.tim.cr1().modify(|_, w| w.cen().clear_bit());
+                    (self.tim, self.pin.release())
+                }
+            }
+
+            impl InputCaptureExt<$TIMX> for $TIMX {
+                fn input_capture<PIN>(
+                    self,
+                    pin: PIN,
+                    edge: SignalEdge,
+                    rcc: &mut Rcc,
+                ) -> InputCapture<$TIMX, PIN>
+                where
+                    PIN: TimerPin<$TIMX>,
+                    PIN::Channel: CaptureChannel<$TIMX>,
+                {
+                    InputCapture::$timX(self, pin, edge, rcc)
+                }
+            }
+        )+
+    };
+}
+
+capture_channels! {
+    TIM1: (Channel1, ccmr1_input, cc1s, ic1f, cc1e, cc1p, cc1np, cc1ie, cc1if, cc1of, ccr1),
+    TIM1: (Channel2, ccmr1_input, cc2s, ic2f, cc2e, cc2p, cc2np, cc2ie, cc2if, cc2of, ccr2),
+    TIM1: (Channel3, ccmr2_input, cc3s, ic3f, cc3e, cc3p, cc3np, cc3ie, cc3if, cc3of, ccr3),
+    TIM1: (Channel4, ccmr2_input, cc4s, ic4f, cc4e, cc4p, cc4np, cc4ie, cc4if, cc4of, ccr4),
+    TIM3: (Channel1, ccmr1_input, cc1s, ic1f, cc1e, cc1p, cc1np, cc1ie, cc1if, cc1of, ccr1),
+    TIM3: (Channel2, ccmr1_input, cc2s, ic2f, cc2e, cc2p, cc2np, cc2ie, cc2if, cc2of, ccr2),
+    TIM3: (Channel3, ccmr2_input, cc3s, ic3f, cc3e, cc3p, cc3np, cc3ie, cc3if, cc3of, ccr3),
+    TIM3: (Channel4, ccmr2_input, cc4s, ic4f, cc4e, cc4p, cc4np, cc4ie, cc4if, cc4of, ccr4),
+}
+
+input_capture! {
+    TIM1: tim1,
+    TIM3: tim3,
+}
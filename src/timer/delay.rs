@@ -20,16 +20,24 @@ pub trait DelayExt<TIM> {
 
 impl Delay<SYST> {
     /// Configures the system timer (SysTick) as a delay provider
-    pub fn syst(mut syst: SYST, rcc: &Rcc) -> Self {
+    pub fn syst(syst: SYST, rcc: &Rcc) -> Self {
+        Self::syst_with_clocks(syst, &rcc.clocks)
+    }
+
+    /// Configures the system timer (SysTick) as a delay provider from frozen `clocks`
+    pub fn syst_with_clocks(mut syst: SYST, clocks: &Clocks) -> Self {
         let clk = match syst.get_clock_source() {
-            SystClkSource::Core => rcc.clocks.ahb_clk,
-            SystClkSource::External => rcc.clocks.core_clk,
+            SystClkSource::Core => clocks.ahb_clk(),
+            SystClkSource::External => clocks.core_clk(),
         };
         Delay { tim: syst, clk }
     }
 
     pub fn delay(&mut self, delay: MicroSecond) {
-        let mut cycles = crate::time::cycles(delay, self.clk);
+        self.delay_cycles(crate::time::cycles(delay, self.clk));
+    }
+
+    fn delay_cycles(&mut self, mut cycles: u32) {
         while cycles > 0 {
             let reload = cmp::min(cycles, 0x00ff_ffff);
             cycles -= reload;
@@ -83,6 +91,14 @@ impl DelayMs<u8> for Delay<SYST> {
     }
 }
 
+impl hal_1::delay::DelayNs for Delay<SYST> {
+    fn delay_ns(&mut self, ns: u32) {
+        // Round up so the delay is never shorter than requested
+        let cycles = (self.clk.raw() as u64 * ns as u64 + 999_999_999) / 1_000_000_000;
+        self.delay_cycles(cycles as u32)
+    }
+}
+
 impl DelayExt<SYST> for SYST {
     fn delay(self, rcc: &mut Rcc) -> Delay<SYST> {
         Delay::syst(self, rcc)
@@ -94,18 +110,24 @@ macro_rules! delays {
         $(
             impl Delay<$TIM> {
                 /// Configures $TIM timer as a delay provider
+                ///
+                /// The timer counts undivided `apb_tim_clk` cycles, so `delay_ns` resolves a
+                /// single timer clock cycle and SysTick is left free for a scheduler.
                 pub fn $tim(tim: $TIM, rcc: &mut Rcc) -> Self {
                     $TIM::enable(rcc);
                     $TIM::reset(rcc);
 
                     Delay {
                         tim,
-                        clk: rcc.clocks.apb_tim_clk,
+                        clk: rcc.clocks.apb_tim_clk(),
                     }
                 }
 
                 pub fn delay(&mut self, delay: MicroSecond) {
-                    let mut cycles = crate::time::cycles(delay, self.clk);
+                    self.delay_cycles(crate::time::cycles(delay, self.clk));
+                }
+
+                fn delay_cycles(&mut self, mut cycles: u32) {
                     while cycles > 0 {
                         let reload = cmp::min(cycles, 0xffff);
                         cycles -= reload;
@@ -159,6 +181,14 @@ macro_rules! delays {
                 }
             }
 
+            impl hal_1::delay::DelayNs for Delay<$TIM> {
+                fn delay_ns(&mut self, ns: u32) {
+                    // Round up so the delay is never shorter than requested
+                    let cycles = (self.clk.raw() as u64 * ns as u64 + 999_999_999) / 1_000_000_000;
+                    self.delay_cycles(cycles as u32)
+                }
+            }
+
             impl DelayExt<$TIM> for $TIM {
                 fn delay(self, rcc: &mut Rcc) -> Delay<$TIM> {
                     Delay::$tim(self, rcc)
@@ -8,6 +8,7 @@ use cortex_m::peripheral::SYST;
 use hal::timer::{CountDown, Periodic};
 use void::Void;
 
+pub mod capture;
 pub mod delay;
 pub mod opm;
 pub mod pins;
@@ -33,7 +34,7 @@ impl Timer<SYST> {
         syst.set_clock_source(SystClkSource::Core);
         Timer {
             tim: syst,
-            clk: rcc.clocks.apb_tim_clk,
+            clk: rcc.clocks.ahb_clk(),
         }
     }
 
@@ -108,7 +109,7 @@ macro_rules! timers {
 
                     Timer {
                         tim,
-                        clk: rcc.clocks.apb_tim_clk,
+                        clk: rcc.clocks.apb_tim_clk(),
                     }
                 }
 
@@ -126,14 +127,14 @@ macro_rules! timers {
                     self.tim.cr1().modify(|_, w| w.cen().set_bit());
                 }
 
-                /// Starts listening
+                /// Starts listening for the update event
                 pub fn listen(&mut self) {
-                    self.tim.dier().write(|w| w.uie().set_bit());
+                    self.tim.dier().modify(|_, w| w.uie().set_bit());
                 }
 
-                /// Stops listening
+                /// Stops listening for the update event
                 pub fn unlisten(&mut self) {
-                    self.tim.dier().write(|w| w.uie().clear_bit());
+                    self.tim.dier().modify(|_, w| w.uie().clear_bit());
                 }
 
                 /// Clears interrupt flag
@@ -141,6 +142,11 @@ macro_rules! timers {
                     self.tim.sr().modify(|_, w| w.uif().clear_bit());
                 }
 
+                /// Returns `true` if the update event is pending
+                pub fn is_irq_pending(&self) -> bool {
+                    self.tim.sr().read().uif().bit_is_set()
+                }
+
                 /// Resets counter value
                 pub fn reset(&mut self) {
                     self.tim.cnt().reset();
@@ -2,7 +2,7 @@
 use crate::rcc::*;
 use crate::stm32::*;
 use crate::time::{Hertz, MicroSecond};
-use crate::timer::pins::TimerPin;
+use crate::timer::pins::{TimerPin, TriggerPin};
 use crate::timer::*;
 use core::marker::PhantomData;
 use fugit::RateExtU32;
@@ -50,7 +50,7 @@ macro_rules! opm {
                 $TIMX::reset(rcc);
 
                 let mut opm = Opm::<$TIMX> {
-                    clk: rcc.clocks.apb_tim_clk,
+                    clk: rcc.clocks.apb_tim_clk(),
                     tim: PhantomData,
                 };
                 opm.set_pulse(pulse);
@@ -58,6 +58,11 @@ macro_rules! opm {
             }
 
             impl Opm<$TIMX> {
+                /// Sets the length of the whole one-pulse cycle
+                ///
+                /// The bound pins go active after their delay (see [`OpmPin::set_delay`]) and
+                /// return inactive at the end of the cycle, so the pulse width is the cycle
+                /// length minus the delay.
                 pub fn set_pulse(&mut self, pulse: MicroSecond) {
                     let pulsehz: Hertz = pulse.into_rate();
                     let cycles_per_period = self.clk / pulsehz;
@@ -74,10 +79,39 @@ macro_rules! opm {
                     }
                 }
 
+                /// Fires a single pulse from software
                 pub fn generate(&mut self) {
                     let tim =  unsafe {&*$TIMX::ptr()};
                     tim.cr1().write(|w| w.opm().set_bit().cen().set_bit());
                 }
+
+                /// Returns the counter tick frequency, to convert pin delays into ticks
+                pub fn tick_freq(&self) -> Hertz {
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    self.clk / (tim.psc().read().bits() as u32 + 1)
+                }
+            }
+        )+
+    }
+}
+
+macro_rules! opm_trigger {
+    ($($TIMX:ident,)+) => {
+        $(
+            impl Opm<$TIMX> {
+                /// Fires a pulse on every edge of the trigger input
+                ///
+                /// The trigger input is selected with [`TriggerPin::new`], the counter is
+                /// started by hardware in trigger mode and stops by itself at the end of the
+                /// cycle.
+                pub fn arm_external_trigger<PIN>(&mut self, _trigger: &TriggerPin<$TIMX, PIN>)
+                where
+                    PIN: TimerPin<$TIMX>,
+                {
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    tim.smcr().modify(|_, w| unsafe { w.sms().bits(0b110) });
+                    tim.cr1().write(|w| w.opm().set_bit());
+                }
             }
         )+
     }
@@ -85,13 +119,17 @@ macro_rules! opm {
 
 macro_rules! opm_hal {
     ($($TIMX:ident:
-        ($CH:ty, $ccxe:ident, $ccmrx_output:ident, $ocxm:ident, $ocxfe:ident, $ccrx:ident),)+
+        ($CH:ty, $ccxe:ident, $ccmrx_output:ident, $ocxm:ident, $ocxfe:ident, $ccrx:ident
+            $(, $moe:ident)*),)+
     ) => {
         $(
             impl OpmPin<$TIMX, $CH> {
                 pub fn enable(&mut self) {
                     let tim =  unsafe {&*$TIMX::ptr()};
                     tim.ccer().modify(|_, w| w.$ccxe().set_bit());
+                    $(
+                        tim.bdtr().modify(|_, w| w.$moe().set_bit());
+                    )*
                     self.setup();
                 }
 
@@ -104,6 +142,7 @@ macro_rules! opm_hal {
                     unsafe { (*$TIMX::ptr()).arr().read().bits() as _ }
                 }
 
+                /// Sets the delay from the start of the cycle to the pulse, in timer ticks
                 pub fn set_delay(&mut self, delay: u32) {
                     self.delay = delay;
                     self.setup();
@@ -122,17 +161,22 @@ macro_rules! opm_hal {
 }
 
 opm_hal! {
-    TIM1: (Channel1, cc1e, ccmr1_output, oc1m, oc1fe, ccr1),
-    TIM1: (Channel2, cc2e, ccmr1_output, oc2m, oc2fe, ccr2),
-    TIM1: (Channel3, cc3e, ccmr2_output, oc3m, oc3fe, ccr3),
-    TIM1: (Channel4, cc4e, ccmr2_output, oc4m, oc4fe, ccr4),
+    TIM1: (Channel1, cc1e, ccmr1_output, oc1m, oc1fe, ccr1, moe),
+    TIM1: (Channel2, cc2e, ccmr1_output, oc2m, oc2fe, ccr2, moe),
+    TIM1: (Channel3, cc3e, ccmr2_output, oc3m, oc3fe, ccr3, moe),
+    TIM1: (Channel4, cc4e, ccmr2_output, oc4m, oc4fe, ccr4, moe),
     TIM3: (Channel1, cc1e, ccmr1_output, oc1m, oc1fe, ccr1),
     TIM3: (Channel2, cc2e, ccmr1_output, oc2m, oc2fe, ccr2),
     TIM3: (Channel3, cc3e, ccmr2_output, oc3m, oc3fe, ccr3),
     TIM3: (Channel4, cc4e, ccmr2_output, oc4m, oc4fe, ccr4),
     TIM14: (Channel1, cc1e, ccmr1_output, oc1m, oc1fe, ccr1),
-    TIM16: (Channel1, cc1e, ccmr1_output, oc1m, oc1fe, ccr1),
-    TIM17: (Channel1, cc1e, ccmr1_output, oc1m, oc1fe, ccr1),
+    TIM16: (Channel1, cc1e, ccmr1_output, oc1m, oc1fe, ccr1, moe),
+    TIM17: (Channel1, cc1e, ccmr1_output, oc1m, oc1fe, ccr1, moe),
+}
+
+opm_trigger! {
+    TIM1,
+    TIM3,
 }
 
 opm! {
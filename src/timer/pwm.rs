@@ -68,7 +68,7 @@ macro_rules! pwm {
                 $TIMX::enable(rcc);
                 $TIMX::reset(rcc);
 
-                let clk = rcc.clocks.apb_tim_clk;
+                let clk = rcc.clocks.apb_tim_clk();
                 let mut pwm = Pwm::<$TIMX> {
                     clk,
                     tim,
@@ -172,10 +172,30 @@ macro_rules! pwm_hal {
                     unsafe { (*$TIMX::ptr()).$ccrx().write(|w| w.bits(duty)) };
                 }
             }
+
+            impl hal_1::pwm::ErrorType for PwmPin<$TIMX, $CH> {
+                type Error = core::convert::Infallible;
+            }
+
+            impl hal_1::pwm::SetDutyCycle for PwmPin<$TIMX, $CH> {
+                fn max_duty_cycle(&self) -> u16 {
+                    // A compare value of ARR + 1 keeps the output active over the whole period
+                    // in PWM mode 1. `set_freq` keeps ARR below 0xffff, a larger ARR written
+                    // outside of it is clamped and can't reach full duty.
+                    let arr = unsafe { (*$TIMX::ptr()).arr().read().bits() };
+                    u16::try_from(arr.saturating_add(1)).unwrap_or(u16::MAX)
+                }
+
+                fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+                    unsafe { (*$TIMX::ptr()).$ccrx().write(|w| w.bits(duty as u32)) };
+                    Ok(())
+                }
+            }
         )+
     };
 }
 
+// `$ccrx` is the zero-based index into the PAC CCR array, `ccr(0)` is CCR1
 macro_rules! pwm_advanced_hal {
     ($($TIMX:ident: (
         $CH:ty,
@@ -224,6 +244,23 @@ macro_rules! pwm_advanced_hal {
                 }
             }
 
+            impl hal_1::pwm::ErrorType for PwmPin<$TIMX, $CH> {
+                type Error = core::convert::Infallible;
+            }
+
+            impl hal_1::pwm::SetDutyCycle for PwmPin<$TIMX, $CH> {
+                fn max_duty_cycle(&self) -> u16 {
+                    // ARR + 1 for full duty in PWM mode 1, `set_freq` keeps ARR below 0xffff
+                    let arr = unsafe { (*$TIMX::ptr()).arr().read().arr().bits() };
+                    arr.saturating_add(1)
+                }
+
+                fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+                    unsafe { (*$TIMX::ptr()).ccr($ccrx).write(|w| w.ccr().bits(duty)) };
+                    Ok(())
+                }
+            }
+
             impl PwmPinMode for PwmPin<$TIMX, $CH>{
                 fn set_compare_mode(&mut self, mode: OutputCompareMode) {
                     unsafe {
@@ -237,13 +274,13 @@ macro_rules! pwm_advanced_hal {
 }
 
 pwm_advanced_hal! {
-    TIM1:  (Channel1, cc1e: cc1ne, ccmr1_output, oc1pe, oc1m, 1, moe),
-    TIM1:  (Channel2, cc2e: cc2ne, ccmr1_output, oc2pe, oc2m, 2, moe),
-    TIM1:  (Channel3, cc3e: cc3ne, ccmr2_output, oc3pe, oc3m, 3, moe),
-    TIM1:  (Channel4, cc4e, ccmr2_output, oc4pe, oc4m, 4, moe),
-    TIM14: (Channel1, cc1e, ccmr1_output, oc1pe, oc1m, 1),
-    TIM16: (Channel1, cc1e: cc1ne, ccmr1_output, oc1pe, oc1m, 1, moe),
-    TIM17: (Channel1, cc1e: cc1ne, ccmr1_output, oc1pe, oc1m, 1, moe),
+    TIM1:  (Channel1, cc1e: cc1ne, ccmr1_output, oc1pe, oc1m, 0, moe),
+    TIM1:  (Channel2, cc2e: cc2ne, ccmr1_output, oc2pe, oc2m, 1, moe),
+    TIM1:  (Channel3, cc3e: cc3ne, ccmr2_output, oc3pe, oc3m, 2, moe),
+    TIM1:  (Channel4, cc4e, ccmr2_output, oc4pe, oc4m, 3, moe),
+    TIM14: (Channel1, cc1e, ccmr1_output, oc1pe, oc1m, 0),
+    TIM16: (Channel1, cc1e: cc1ne, ccmr1_output, oc1pe, oc1m, 0, moe),
+    TIM17: (Channel1, cc1e: cc1ne, ccmr1_output, oc1pe, oc1m, 0, moe),
 }
 
 pwm_hal! {
@@ -5,6 +5,17 @@ use crate::rcc::*;
 use crate::timer::pins::TimerPin;
 use crate::timer::*;
 
+/// Encoder edges the counter counts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QeiMode {
+    /// Count on TI2 edges only (SMS = 001)
+    Ti2,
+    /// Count on TI1 edges only (SMS = 010)
+    Ti1,
+    /// Count on both TI1 and TI2 edges, four counts per encoder cycle (SMS = 011)
+    Both,
+}
+
 pub struct Qei<TIM, PINS> {
     tim: TIM,
     pins: PINS,
@@ -74,6 +85,21 @@ macro_rules! qei {
                     Qei { tim, pins }
                 }
 
+                /// Selects the edges the position is counted on
+                pub fn set_mode(&mut self, mode: QeiMode) {
+                    let sms = match mode {
+                        QeiMode::Ti2 => 0b001,
+                        QeiMode::Ti1 => 0b010,
+                        QeiMode::Both => 0b011,
+                    };
+                    self.tim.smcr().modify(|_, w| unsafe { w.sms().bits(sms) });
+                }
+
+                /// Sets the position counter to zero
+                pub fn reset(&mut self) {
+                    self.tim.cnt().reset();
+                }
+
                 pub fn release(self) -> ($TIMX, PINS) {
                     (self.tim, self.pins.release())
                 }
@@ -95,6 +121,16 @@ macro_rules! qei {
                 }
             }
 
+            impl Timer<$TIMX> {
+                /// Turns the timer into a quadrature encoder interface on channels 1 and 2
+                pub fn into_encoder<PINS>(self, pins: PINS, rcc: &mut Rcc) -> Qei<$TIMX, PINS>
+                where
+                    PINS: QeiPins<$TIMX>,
+                {
+                    Qei::$tim(self.release(), pins, rcc)
+                }
+            }
+
             impl<PINS> QeiExt<$TIMX, PINS> for $TIMX where PINS: QeiPins<$TIMX> {
                 fn qei(self, pins: PINS, rcc: &mut Rcc) -> Qei<$TIMX, PINS> {
                     Qei::$tim(self, pins, rcc)
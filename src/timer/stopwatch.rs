@@ -4,6 +4,7 @@ use crate::time::{duration, Hertz, Instant, MicroSecond};
 
 pub trait StopwatchExt<TIM> {
     fn stopwatch(self, rcc: &mut Rcc) -> Stopwatch<TIM>;
+    fn stopwatch_micros(self, rcc: &mut Rcc) -> Stopwatch<TIM>;
 }
 
 pub struct Stopwatch<TIM> {
@@ -16,17 +17,29 @@ macro_rules! stopwatches {
         $(
             impl Stopwatch<$TIM> {
                 pub fn $tim(tim: $TIM, rcc: &mut Rcc) -> Self {
-                    assert!(rcc.clocks.apb_tim_clk.raw() > 1_000_000);
+                    assert!(rcc.clocks.apb_tim_clk().raw() > 1_000_000);
                     $TIM::enable(rcc);
                     $TIM::reset(rcc);
 
                     tim.cr1().modify(|_, w| w.cen().set_bit());
                     Stopwatch {
                         tim,
-                        clk: rcc.clocks.apb_tim_clk,
+                        clk: rcc.clocks.apb_tim_clk(),
                     }
                 }
 
+                /// Configures the timer as a stopwatch counting microseconds
+                ///
+                /// The counter wraps every 65.536 ms, `elapsed` handles a single wrap. Panics if
+                /// the APB timer clock is not a multiple of 1 MHz.
+                pub fn microseconds(tim: $TIM, rcc: &mut Rcc) -> Self {
+                    let clk = rcc.clocks.apb_tim_clk().raw();
+                    assert!(clk % 1_000_000 == 0);
+                    let mut stopwatch = Self::$tim(tim, rcc);
+                    stopwatch.set_prescaler((clk / 1_000_000 - 1) as u16);
+                    stopwatch
+                }
+
                 /// Overrides the counter clock input frequency
                 ///
                 /// Useful if the APB Timer Clock changes after the `Stopwatch` is created or
@@ -85,6 +98,10 @@ macro_rules! stopwatches {
                 fn stopwatch(self, rcc: &mut Rcc) -> Stopwatch<$TIM> {
                     Stopwatch::$tim(self, rcc)
                 }
+
+                fn stopwatch_micros(self, rcc: &mut Rcc) -> Stopwatch<$TIM> {
+                    Stopwatch::<$TIM>::microseconds(self, rcc)
+                }
             }
         )+
     }
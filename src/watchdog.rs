@@ -1,30 +1,43 @@
+//! Independent and window watchdogs
 use crate::prelude::*;
-use crate::rcc::{Enable, Rcc};
+use crate::rcc::{Enable, Rcc, LSI_FREQ};
 use crate::stm32::{IWDG, WWDG};
 use crate::time::{Hertz, MicroSecond};
 use hal::watchdog;
 
-pub struct IndependedWatchdog {
+/// Independent watchdog, clocked by the LSI
+pub struct IndependentWatchdog {
     iwdg: IWDG,
 }
 
-impl IndependedWatchdog {
+#[deprecated(note = "renamed to `IndependentWatchdog`")]
+pub type IndependedWatchdog = IndependentWatchdog;
+
+impl IndependentWatchdog {
+    /// Longest timeout reachable with the largest prescaler and reload value
+    pub fn max_timeout() -> MicroSecond {
+        let ticks: u64 = (0xfff + 1) * (4 << 6);
+        MicroSecond::from_ticks((ticks * 1_000_000 / LSI_FREQ as u64) as u32)
+    }
+
     pub fn feed(&mut self) {
         self.iwdg.kr().write(|w| unsafe { w.key().bits(0xaaaa) });
     }
 
+    /// Starts the watchdog, the MCU is reset unless [`Self::feed()`] is called within `period`
+    ///
+    /// The LSI is not trimmed, the actual timeout can be a few percent off. Once started the
+    /// watchdog can only be stopped by a reset.
     pub fn start(&mut self, period: MicroSecond) {
-        let mut cycles = crate::time::cycles(period, (32_768 / 4).Hz());
+        assert!(period <= Self::max_timeout());
+
+        let mut cycles = crate::time::cycles(period, (LSI_FREQ / 4).Hz());
         let mut psc = 0;
-        let mut reload = 0;
-        while psc < 6 {
-            reload = cycles;
-            if reload <= 0xfff {
-                break;
-            }
+        while psc < 6 && cycles > 0xfff + 1 {
             psc += 1;
             cycles /= 2;
         }
+        let reload = cycles.clamp(1, 0xfff + 1) - 1;
 
         // Enable watchdog
         self.iwdg.kr().write(|w| unsafe { w.key().bits(0xcccc) });
@@ -43,13 +56,13 @@ impl IndependedWatchdog {
     }
 }
 
-impl watchdog::Watchdog for IndependedWatchdog {
+impl watchdog::Watchdog for IndependentWatchdog {
     fn feed(&mut self) {
         self.feed();
     }
 }
 
-impl watchdog::WatchdogEnable for IndependedWatchdog {
+impl watchdog::WatchdogEnable for IndependentWatchdog {
     type Time = MicroSecond;
 
     fn start<T>(&mut self, period: T)
@@ -61,65 +74,100 @@ impl watchdog::WatchdogEnable for IndependedWatchdog {
 }
 
 pub trait IWDGExt {
-    fn constrain(self) -> IndependedWatchdog;
+    fn constrain(self) -> IndependentWatchdog;
 }
 
-impl IndependedWatchdog {
+impl IndependentWatchdog {
     pub fn release(self) -> IWDG {
         self.iwdg
     }
 }
 
 impl IWDGExt for IWDG {
-    fn constrain(self) -> IndependedWatchdog {
-        IndependedWatchdog { iwdg: self }
+    fn constrain(self) -> IndependentWatchdog {
+        IndependentWatchdog { iwdg: self }
     }
 }
 
+/// Window watchdog, clocked by the APB clock divided by 4096
+///
+/// The MCU is reset if the watchdog is not fed before the timeout, and also if it is fed too
+/// early, before the window opens. Both limits are set by [`WindowWatchdog::start()`].
 pub struct WindowWatchdog {
     wwdg: WWDG,
     clk: Hertz,
+    counter: u8,
 }
 
 impl WindowWatchdog {
+    /// Reloads the counter
+    ///
+    /// Feeding while the window is still closed resets the MCU, which is the point of a
+    /// window watchdog, use [`Self::can_feed()`] to check it first if needed.
     pub fn feed(&mut self) {
-        self.wwdg.cr().write(|w| unsafe { w.t().bits(0xff) });
+        self.wwdg.cr().write(|w| w.t().set(self.counter));
     }
 
-    pub fn set_window(&mut self, window: MicroSecond) {
-        let mut cycles = crate::time::cycles(window, self.clk);
-        let mut psc = 0u8;
-        let mut window = 0;
-        while psc < 8 {
-            window = cycles;
-            if window <= 0x40 {
-                break;
-            }
-            psc += 1;
-            cycles /= 2;
-        }
-        assert!(window <= 0x40);
-        self.wwdg
-            .cfr()
-            .write(|w| unsafe { w.wdgtb().bits(psc).w().bits(window as u8) });
+    /// Returns `true` if the window is open and feeding is allowed
+    pub fn can_feed(&self) -> bool {
+        self.wwdg.cr().read().t().bits() <= self.wwdg.cfr().read().w().bits()
     }
 
+    /// Enables the early wakeup interrupt, fired one tick before the reset
+    ///
+    /// The interrupt can only be disabled by a reset.
     pub fn listen(&mut self) {
-        self.wwdg.cfr().write(|w| w.ewi().set_bit());
+        self.wwdg.cfr().modify(|_, w| w.ewi().set_bit());
+    }
+
+    /// Returns `true` if the early wakeup interrupt is pending
+    pub fn is_pending(&self) -> bool {
+        self.wwdg.sr().read().ewif().bit_is_set()
     }
 
-    pub fn unlisten(&mut self) {
-        self.wwdg.cfr().write(|w| w.ewi().clear_bit());
+    /// Clears the early wakeup interrupt flag
+    pub fn unpend(&mut self) {
+        self.wwdg.sr().write(|w| w.ewif().clear_bit());
     }
 
     pub fn release(self) -> WWDG {
         self.wwdg
     }
 
-    pub fn start(&mut self, period: MicroSecond) {
-        self.set_window(period);
-        self.feed();
-        self.wwdg.cr().write(|w| w.wdga().set_bit());
+    /// Starts the watchdog, it must then be fed between `window` and `timeout` after the
+    /// previous feed or the MCU is reset
+    ///
+    /// A zero `window` allows feeding right away. The prescaler is the smallest one reaching
+    /// `timeout`, which gives the best resolution, the longest timeout is 64 * 4096 * 128 APB
+    /// cycles. Once started the watchdog can only be stopped by a reset.
+    pub fn start(&mut self, window: MicroSecond, timeout: MicroSecond) {
+        assert!(window < timeout);
+
+        let mut psc = 0u8;
+        let mut ticks = crate::time::cycles(timeout, self.clk);
+        while psc < 7 && ticks > 0x40 {
+            psc += 1;
+            ticks = crate::time::cycles(timeout, (self.clk.raw() >> psc).Hz());
+        }
+        assert!(ticks <= 0x40);
+        let ticks = ticks.max(1);
+
+        let closed = if window.ticks() == 0 {
+            0
+        } else {
+            crate::time::cycles(window, (self.clk.raw() >> psc).Hz())
+        };
+        assert!(closed < ticks);
+
+        // the reset fires when T6 clears, T counts down from 0x40 + ticks - 1
+        self.counter = (0x40 + ticks - 1) as u8;
+        let window = self.counter - closed as u8;
+        self.wwdg
+            .cfr()
+            .modify(|_, w| unsafe { w.wdgtb().bits(psc).w().set(window) });
+        self.wwdg
+            .cr()
+            .write(|w| w.t().set(self.counter).wdga().set_bit());
     }
 }
 
@@ -136,7 +184,7 @@ impl watchdog::WatchdogEnable for WindowWatchdog {
     where
         T: Into<MicroSecond>,
     {
-        self.start(period.into())
+        self.start(0.micros(), period.into())
     }
 }
 
@@ -147,10 +195,11 @@ pub trait WWDGExt {
 impl WWDGExt for WWDG {
     fn constrain(self, rcc: &mut Rcc) -> WindowWatchdog {
         WWDG::enable(rcc);
-        let clk = rcc.clocks.apb_clk.raw() / 4096;
+        let clk = rcc.clocks.apb_clk().raw() / 4096;
         WindowWatchdog {
             wwdg: self,
             clk: clk.Hz(),
+            counter: 0x7f,
         }
     }
 }